@@ -113,12 +113,19 @@ if config_env() == :prod do
   host = System.get_env("PHX_HOST") || "localhost"
   port = String.to_integer(System.get_env("PORT") || "4000")
 
+  # The launcher listens on all interfaces when the backend is reached on another host
+  bind_ip =
+    case :inet.parse_address(String.to_charlist(System.get_env("BIND_IP") || "127.0.0.1")) do
+      {:ok, ip} -> ip
+      {:error, _} -> raise "environment variable BIND_IP is not a valid IP address"
+    end
+
   config :trenino, :dns_cluster_query, System.get_env("DNS_CLUSTER_QUERY")
 
   config :trenino, TreninoWeb.Endpoint,
     url: [host: host, port: port, scheme: "http"],
     http: [
-      ip: {127, 0, 0, 1},
+      ip: bind_ip,
       port: port
     ],
     secret_key_base: secret_key_base,
@@ -103,7 +103,11 @@ defmodule Trenino.Keyboard do
   defp execute(action, keystroke) do
     case executable_path() do
       {:ok, path} ->
-        case System.cmd(path, [action, keystroke], stderr_to_stdout: true) do
+        # Captured bindings name keys in uppercase ("W", "CTRL+S"), so they
+        # must not be read as Shift+letter
+        args = [action, keystroke, "--no-auto-shift"]
+
+        case System.cmd(path, args, stderr_to_stdout: true) do
           {_output, 0} ->
             :ok
 
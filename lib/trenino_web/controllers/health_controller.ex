@@ -6,6 +6,9 @@ defmodule TreninoWeb.HealthController do
   - Database is accessible
   - Migrations are complete (by querying a known table)
 
+  The ready response also carries the backend version, which the desktop
+  app lists next to its own to spot version skew.
+
   Used by Tauri to wait for full app startup before showing the main window.
   """
 
@@ -13,13 +16,14 @@ defmodule TreninoWeb.HealthController do
 
   import Ecto.Query
 
+  alias Trenino.AppVersion
   alias Trenino.Firmware.FirmwareRelease
   alias Trenino.Repo
 
   def index(conn, _params) do
     case check_database_ready() do
       :ok ->
-        json(conn, %{status: "ok"})
+        json(conn, %{status: "ok", version: AppVersion.current_version()})
 
       {:error, reason} ->
         conn
@@ -0,0 +1,103 @@
+//! `keystroke autorepeat`: hold a key and let it repeat like a physical one.
+//!
+//! On X11 the server repeats held keys itself, synthetic ones included. On
+//! Windows and macOS only hardware keys repeat, so the repeats are sent here
+//! as further key-down events, at the system's repeat delay and rate where it
+//! can be read.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use enigo::{Direction, Enigo, Key};
+
+use crate::{press_combo, release_combo, Error, ReleaseOrder, SendKey};
+
+/// Hold `modifiers` and `key` for `duration`, with the key repeating meanwhile
+pub fn hold(
+    enigo: &mut Enigo,
+    modifiers: &[Key],
+    key: Key,
+    duration: Duration,
+) -> Result<(), Error> {
+    let deadline = Instant::now() + duration;
+    press_combo(enigo, modifiers, Some(key), 0)?;
+
+    let result = match platform::repeat_timing() {
+        None => {
+            thread::sleep(duration);
+            Ok(())
+        }
+        Some((delay, interval)) => {
+            log::debug!(
+                "Repeating {:?} after {:?}, every {:?}",
+                key,
+                delay,
+                interval
+            );
+            repeat_until(enigo, key, deadline, delay, interval)
+        }
+    };
+
+    release_combo(enigo, modifiers, Some(key), 0, ReleaseOrder::Reverse).and(result)
+}
+
+/// Send key-down events for `key` from `delay` on, every `interval`
+fn repeat_until(
+    enigo: &mut Enigo,
+    key: Key,
+    deadline: Instant,
+    delay: Duration,
+    interval: Duration,
+) -> Result<(), Error> {
+    let mut next = Instant::now() + delay;
+    while next < deadline {
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+        key.send(enigo, Direction::Press)?;
+        next += interval;
+    }
+    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::time::Duration;
+
+    /// None, since the X server repeats the key itself
+    pub fn repeat_timing() -> Option<(Duration, Duration)> {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::time::Duration;
+
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETKEYBOARDDELAY, SPI_GETKEYBOARDSPEED,
+    };
+
+    /// The repeat delay and interval from the keyboard settings
+    pub fn repeat_timing() -> Option<(Duration, Duration)> {
+        let (mut delay, mut speed) = (1u32, 31u32);
+        // SAFETY: both settings are written to a u32 that outlives the call
+        unsafe {
+            SystemParametersInfoW(SPI_GETKEYBOARDDELAY, 0, &mut delay as *mut u32 as _, 0);
+            SystemParametersInfoW(SPI_GETKEYBOARDSPEED, 0, &mut speed as *mut u32 as _, 0);
+        }
+        // Delay 0-3 is 250ms to 1s, speed 0-31 is about 2.5 to 30 repeats a second
+        let delay = Duration::from_millis(250 * (u64::from(delay.min(3)) + 1));
+        let rate = 2.5 + f64::from(speed.min(31)) * 27.5 / 31.0;
+        Some((delay, Duration::from_secs_f64(1.0 / rate)))
+    }
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+mod platform {
+    use std::time::Duration;
+
+    /// A typical keyboard's delay and rate, as the user's settings aren't read
+    pub fn repeat_timing() -> Option<(Duration, Duration)> {
+        Some((Duration::from_millis(500), Duration::from_millis(33)))
+    }
+}
@@ -0,0 +1,56 @@
+//! User key-alias files for `--config`.
+//!
+//! Aliases give sim-specific names to existing keys or combinations and are
+//! resolved before the built-in key table:
+//!
+//! ```toml
+//! [aliases]
+//! HORN = "H"
+//! BELL = "B"
+//! STARTUP = "CTRL+SHIFT+E"
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::keys::{parse_key_parts, ParseOptions};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/// Read an alias file, checking that every alias expands to one valid
+/// combination of built-in keys
+pub fn load_aliases(path: &Path) -> Result<HashMap<String, String>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let config: Config = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid config '{}': {}", path.display(), e))?;
+
+    // Power keys are checked where the alias is used, against --allow-power
+    let builtin = ParseOptions {
+        strict: true,
+        chord: true,
+        allow_power: true,
+        ..ParseOptions::default()
+    };
+    let mut aliases = HashMap::new();
+    for (name, combo) in config.aliases {
+        if combo.contains(',') {
+            return Err(format!(
+                "Alias '{}' must expand to a single combination, got '{}'",
+                name, combo
+            ));
+        }
+        let parts: Vec<&str> = combo.split('+').collect();
+        parse_key_parts(&parts, &builtin).map_err(|e| format!("Alias '{}': {}", name, e))?;
+        aliases.insert(name.to_uppercase(), combo);
+    }
+
+    Ok(aliases)
+}
@@ -0,0 +1,225 @@
+//! `keystroke doctor`: report what enigo is using and whether input works.
+
+use std::path::PathBuf;
+
+use enigo::{Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+use serde_json::json;
+
+use crate::OutputFormat;
+
+/// The enigo backend this binary was built with
+#[cfg(target_os = "windows")]
+const BACKEND: &str = "Windows (SendInput)";
+#[cfg(target_os = "macos")]
+const BACKEND: &str = "macOS (CoreGraphics)";
+#[cfg(all(unix, not(target_os = "macos")))]
+const BACKEND: &str = "X11 (x11rb)";
+
+/// What to do when input doesn't reach native Wayland applications
+const WAYLAND_REMEDIATION: &str = "\
+To reach applications running natively under Wayland:
+  - log in to an X11 session instead (e.g. \"GNOME on Xorg\"), or
+  - run the sim under XWayland; Proton and Wine games usually are, or
+  - use ydotool, which injects input through /dev/uinput: install it, start
+    ydotoold and give your user write access to /dev/uinput (e.g. through
+    the input group or a udev rule)";
+
+/// Why uinput is only reported on, not used
+const UINPUT_LIMITATION: &str = "keystroke can't send input through /dev/uinput itself: \
+    enigo has no uinput backend, so there is no --backend uinput. The uinput check \
+    tells whether ydotool can use it.";
+
+/// Outcome of one diagnostic step
+struct Check {
+    name: &'static str,
+    result: Result<(), String>,
+}
+
+/// Run the diagnostics and print a report. Returns whether every check passed.
+pub fn doctor(settings: &Settings, format: OutputFormat) -> bool {
+    let checks = run_checks(settings);
+    let session = session();
+    let wayland = is_wayland_session(&session);
+    // Only worth knowing about when X11 events don't reach everything
+    let wayland_paths = if wayland { wayland_paths() } else { Vec::new() };
+
+    match format {
+        OutputFormat::Text => {
+            println!("OS: {} ({})", std::env::consts::OS, std::env::consts::ARCH);
+            println!("Backend: {}", BACKEND);
+            for (name, value) in &session {
+                println!("{}: {}", name, value.as_deref().unwrap_or("(unset)"));
+            }
+            for check in &checks {
+                match &check.result {
+                    Ok(()) => println!("{}: ok", check.name),
+                    Err(e) => println!("{}: failed ({})", check.name, e),
+                }
+            }
+            for path in &wayland_paths {
+                match &path.result {
+                    Ok(()) => println!("{}: usable", path.name),
+                    Err(e) => println!("{}: not usable ({})", path.name, e),
+                }
+            }
+            if wayland {
+                println!();
+                println!(
+                    "Note: this is a Wayland session. Input is sent through XWayland and \
+                     only reaches applications running under X11."
+                );
+                println!("{}", WAYLAND_REMEDIATION);
+                println!();
+                println!("Note: {}", UINPUT_LIMITATION);
+            }
+        }
+        OutputFormat::Json => {
+            let session: serde_json::Map<_, _> = session
+                .iter()
+                .map(|(name, value)| (name.to_string(), json!(value)))
+                .collect();
+            let checks: serde_json::Map<_, _> = checks
+                .iter()
+                .map(|check| {
+                    let value = json!({
+                        "ok": check.result.is_ok(),
+                        "error": check.result.as_ref().err(),
+                    });
+                    (check.name.to_lowercase().replace(' ', "_"), value)
+                })
+                .collect();
+            let wayland_paths: serde_json::Map<_, _> = wayland_paths
+                .iter()
+                .map(|path| {
+                    let value = json!({
+                        "usable": path.result.is_ok(),
+                        "error": path.result.as_ref().err(),
+                    });
+                    (path.name.to_string(), value)
+                })
+                .collect();
+            let report = json!({
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+                "backend": BACKEND,
+                "session": session,
+                "checks": checks,
+                "wayland": wayland.then(|| json!({
+                    "paths": wayland_paths,
+                    "remediation": WAYLAND_REMEDIATION,
+                    "uinput_backend": false,
+                    "uinput_note": UINPUT_LIMITATION,
+                })),
+            });
+            println!("{}", report);
+        }
+    }
+
+    checks.iter().all(|check| check.result.is_ok())
+}
+
+fn run_checks(settings: &Settings) -> Vec<Check> {
+    let mut enigo = match Enigo::new(settings) {
+        Ok(enigo) => enigo,
+        Err(e) => {
+            return vec![Check {
+                name: "Connection",
+                result: Err(e.to_string()),
+            }]
+        }
+    };
+
+    vec![
+        Check {
+            name: "Connection",
+            result: Ok(()),
+        },
+        Check {
+            // A lone Shift has no effect on the focused application
+            name: "Key press",
+            result: enigo
+                .key(Key::Shift, Direction::Click)
+                .map_err(|e| e.to_string()),
+        },
+        Check {
+            // Nudge the pointer and put it straight back
+            name: "Mouse move",
+            result: enigo
+                .move_mouse(1, 0, Coordinate::Rel)
+                .and_then(|()| enigo.move_mouse(-1, 0, Coordinate::Rel))
+                .map_err(|e| e.to_string()),
+        },
+    ]
+}
+
+/// Environment variables that decide which display server input goes to
+fn session() -> Vec<(&'static str, Option<String>)> {
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        ["XDG_SESSION_TYPE", "WAYLAND_DISPLAY", "DISPLAY"]
+            .into_iter()
+            .map(|name| (name, std::env::var(name).ok()))
+            .collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// A warning for Wayland sessions, where input only reaches XWayland
+/// applications, pointing at `doctor` for the ways around it
+pub fn wayland_warning() -> Option<&'static str> {
+    is_wayland_session(&session()).then_some(
+        "this is a Wayland session, input only reaches applications running under \
+         XWayland. Run `keystroke doctor` for how to reach the others",
+    )
+}
+
+/// Ways around XWayland for input to native Wayland applications
+fn wayland_paths() -> Vec<Check> {
+    vec![
+        Check {
+            name: "uinput",
+            result: std::fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/uinput")
+                .map(|_| ())
+                .map_err(|e| format!("/dev/uinput: {}", e)),
+        },
+        Check {
+            name: "ydotool",
+            result: ydotool_status(),
+        },
+    ]
+}
+
+/// Whether ydotool is installed and its daemon is listening
+fn ydotool_status() -> Result<(), String> {
+    let installed = std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| dir.join("ydotool").is_file())
+    });
+    if !installed {
+        return Err("ydotool is not installed".to_string());
+    }
+
+    // ydotoold listens on $YDOTOOL_SOCKET, or in the runtime directory or /tmp
+    let sockets: Vec<PathBuf> = match std::env::var_os("YDOTOOL_SOCKET") {
+        Some(socket) => vec![socket.into()],
+        None => std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join(".ydotool_socket"))
+            .into_iter()
+            .chain([PathBuf::from("/tmp/.ydotool_socket")])
+            .collect(),
+    };
+    if sockets.iter().any(|socket| socket.exists()) {
+        Ok(())
+    } else {
+        Err("ydotoold is not running".to_string())
+    }
+}
+
+fn is_wayland_session(session: &[(&str, Option<String>)]) -> bool {
+    session.iter().any(|(name, value)| match *name {
+        "XDG_SESSION_TYPE" => value.as_deref() == Some("wayland"),
+        "WAYLAND_DISPLAY" => value.is_some(),
+        _ => false,
+    })
+}
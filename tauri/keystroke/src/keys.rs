@@ -0,0 +1,933 @@
+//! Key name table and key combination parser.
+//!
+//! Every named token the parser accepts lives in [`KEY_GROUPS`], which is also
+//! what `keystroke list-keys` prints, so the two can't drift apart.
+
+use std::collections::HashMap;
+
+use enigo::Key;
+
+/// What a named token resolves to
+#[derive(Clone, Copy)]
+pub enum KeyToken {
+    /// Held while the main key is pressed
+    Modifier(Key),
+    /// The key being pressed
+    Main(Key),
+    /// A main key that puts the machine to sleep or powers it off, only
+    /// accepted with `ParseOptions::allow_power`
+    Power(Key),
+    /// A known key that enigo can't send on this platform
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    Unsupported,
+}
+
+/// A key and every name it can be referred to by
+pub struct NamedKey {
+    pub names: &'static [&'static str],
+    pub token: KeyToken,
+}
+
+const fn modifier(names: &'static [&'static str], key: Key) -> NamedKey {
+    NamedKey {
+        names,
+        token: KeyToken::Modifier(key),
+    }
+}
+
+const fn main(names: &'static [&'static str], key: Key) -> NamedKey {
+    NamedKey {
+        names,
+        token: KeyToken::Main(key),
+    }
+}
+
+const fn power(names: &'static [&'static str], key: Key) -> NamedKey {
+    NamedKey {
+        names,
+        token: KeyToken::Power(key),
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const fn unsupported(names: &'static [&'static str]) -> NamedKey {
+    NamedKey {
+        names,
+        token: KeyToken::Unsupported,
+    }
+}
+
+// Side-specific modifiers that enigo names differently on each platform
+#[cfg(target_os = "windows")]
+const LEFT_ALT: Key = Key::LMenu;
+#[cfg(target_os = "windows")]
+const RIGHT_ALT: Key = Key::RMenu;
+#[cfg(target_os = "windows")]
+const RIGHT_META: Key = Key::RWin;
+
+#[cfg(target_os = "macos")]
+const LEFT_ALT: Key = Key::Alt;
+#[cfg(target_os = "macos")]
+const RIGHT_ALT: Key = Key::ROption;
+#[cfg(target_os = "macos")]
+const RIGHT_META: Key = Key::RCommand;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const LEFT_ALT: Key = Key::Alt;
+#[cfg(all(unix, not(target_os = "macos")))]
+const RIGHT_ALT: Key = Key::Other(0xffea); // XK_Alt_R
+#[cfg(all(unix, not(target_os = "macos")))]
+const RIGHT_META: Key = Key::Other(0xffec); // XK_Super_R
+
+/// Named keys grouped by category. Function keys are handled separately
+/// by [`function_key`].
+pub const KEY_GROUPS: &[(&str, &[NamedKey])] = &[
+    (
+        // Generic names are aliases for the left-hand key
+        "Modifiers",
+        &[
+            modifier(&["CTRL", "CONTROL", "LCTRL"], Key::LControl),
+            modifier(&["RCTRL"], Key::RControl),
+            modifier(&["SHIFT", "LSHIFT"], Key::LShift),
+            modifier(&["RSHIFT"], Key::RShift),
+            modifier(&["ALT", "LALT", "OPT", "OPTION"], LEFT_ALT),
+            modifier(&["RALT"], RIGHT_ALT),
+            modifier(&["META", "WIN", "SUPER", "LMETA", "CMD"], Key::Meta),
+            modifier(&["RMETA"], RIGHT_META),
+        ],
+    ),
+    (
+        "Special keys",
+        &[
+            main(&["SPACE", "SPACEBAR"], Key::Space),
+            main(&["ENTER", "RETURN", "CR", "NL"], Key::Return),
+            main(&["TAB"], Key::Tab),
+            main(&["ESCAPE", "ESC"], Key::Escape),
+            main(&["BACKSPACE", "BKSP"], Key::Backspace),
+            main(&["DELETE", "DEL"], Key::Delete),
+            #[cfg(not(target_os = "macos"))]
+            main(&["INSERT", "INS"], Key::Insert),
+            #[cfg(target_os = "macos")]
+            unsupported(&["INSERT", "INS"]),
+            main(&["HOME"], Key::Home),
+            main(&["END"], Key::End),
+            main(&["PAGEUP", "PGUP"], Key::PageUp),
+            main(&["PAGEDOWN", "PGDN"], Key::PageDown),
+        ],
+    ),
+    (
+        "System keys",
+        &[
+            #[cfg(not(target_os = "macos"))]
+            main(&["PRINTSCREEN", "PRTSC"], Key::PrintScr),
+            #[cfg(not(target_os = "macos"))]
+            main(&["PAUSE", "BREAK"], Key::Pause),
+            #[cfg(target_os = "windows")]
+            main(&["CONTEXTMENU", "MENU", "APPS"], Key::Apps),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            main(&["CONTEXTMENU", "MENU", "APPS"], Key::LMenu), // XK_Menu
+            #[cfg(target_os = "macos")]
+            unsupported(&["PRINTSCREEN", "PRTSC"]),
+            #[cfg(target_os = "macos")]
+            unsupported(&["PAUSE", "BREAK"]),
+            #[cfg(target_os = "macos")]
+            unsupported(&["CONTEXTMENU", "MENU", "APPS"]),
+        ],
+    ),
+    (
+        // Refused unless --allow-power is given, see `KeyToken::Power`
+        "Power keys",
+        &[
+            #[cfg(target_os = "windows")]
+            power(&["SLEEP"], Key::Sleep),
+            #[cfg(target_os = "windows")]
+            unsupported(&["POWER"]),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            power(&["SLEEP"], Key::Other(0x1008ff2f)), // XF86XK_Sleep
+            #[cfg(all(unix, not(target_os = "macos")))]
+            power(&["POWER"], Key::Other(0x1008ff2a)), // XF86XK_PowerOff
+            #[cfg(target_os = "macos")]
+            unsupported(&["SLEEP"]),
+            #[cfg(target_os = "macos")]
+            power(&["POWER"], Key::Power),
+        ],
+    ),
+    (
+        "Arrow keys",
+        &[
+            main(&["UP", "ARROWUP"], Key::UpArrow),
+            main(&["DOWN", "ARROWDOWN"], Key::DownArrow),
+            main(&["LEFT", "ARROWLEFT"], Key::LeftArrow),
+            main(&["RIGHT", "ARROWRIGHT"], Key::RightArrow),
+        ],
+    ),
+    (
+        // Named so they don't clash with combo/sequence separators
+        "Punctuation",
+        &[
+            main(&["COMMA"], Key::Unicode(',')),
+            main(&["PERIOD"], Key::Unicode('.')),
+            main(&["SEMICOLON"], Key::Unicode(';')),
+            main(&["SLASH"], Key::Unicode('/')),
+            main(&["BACKSLASH"], Key::Unicode('\\')),
+            main(&["MINUS"], Key::Unicode('-')),
+            main(&["EQUALS"], Key::Unicode('=')),
+            main(&["LEFTBRACKET"], Key::Unicode('[')),
+            main(&["RIGHTBRACKET"], Key::Unicode(']')),
+            main(&["GRAVE"], Key::Unicode('`')),
+        ],
+    ),
+    (
+        "Lock keys",
+        &[
+            main(&["CAPSLOCK", "CAPS"], Key::CapsLock),
+            #[cfg(not(target_os = "macos"))]
+            main(&["NUMLOCK"], Key::Numlock),
+            #[cfg(target_os = "windows")]
+            main(&["SCROLLLOCK"], Key::Scroll),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            main(&["SCROLLLOCK"], Key::ScrollLock),
+            #[cfg(target_os = "macos")]
+            unsupported(&["NUMLOCK"]),
+            #[cfg(target_os = "macos")]
+            unsupported(&["SCROLLLOCK"]),
+        ],
+    ),
+    (
+        "Media keys",
+        &[
+            main(&["VOLUMEUP"], Key::VolumeUp),
+            main(&["VOLUMEDOWN"], Key::VolumeDown),
+            main(&["MUTE"], Key::VolumeMute),
+            main(&["PLAYPAUSE"], Key::MediaPlayPause),
+            main(&["NEXTTRACK"], Key::MediaNextTrack),
+            main(&["PREVTRACK"], Key::MediaPrevTrack),
+            #[cfg(not(target_os = "macos"))]
+            main(&["STOP"], Key::MediaStop),
+            #[cfg(target_os = "macos")]
+            unsupported(&["STOP"]),
+        ],
+    ),
+    (
+        "Numpad",
+        &[
+            main(&["NUMPAD0"], Key::Numpad0),
+            main(&["NUMPAD1"], Key::Numpad1),
+            main(&["NUMPAD2"], Key::Numpad2),
+            main(&["NUMPAD3"], Key::Numpad3),
+            main(&["NUMPAD4"], Key::Numpad4),
+            main(&["NUMPAD5"], Key::Numpad5),
+            main(&["NUMPAD6"], Key::Numpad6),
+            main(&["NUMPAD7"], Key::Numpad7),
+            main(&["NUMPAD8"], Key::Numpad8),
+            main(&["NUMPAD9"], Key::Numpad9),
+            main(&["NUMPADADD"], Key::Add),
+            main(&["NUMPADSUBTRACT"], Key::Subtract),
+            main(&["NUMPADMULTIPLY"], Key::Multiply),
+            main(&["NUMPADDIVIDE"], Key::Divide),
+            main(&["NUMPADDECIMAL"], Key::Decimal),
+            // enigo has no dedicated variant, so send the platform keycode
+            #[cfg(all(unix, not(target_os = "macos")))]
+            main(&["NUMPADENTER"], Key::Other(0xff8d)), // XK_KP_Enter
+            #[cfg(target_os = "macos")]
+            main(&["NUMPADENTER"], Key::Other(0x4c)), // kVK_ANSI_KeypadEnter
+            // enigo can't set the extended-key flag that tells it apart from
+            // Enter; `--raw 0xFF1C` sends it
+            #[cfg(target_os = "windows")]
+            unsupported(&["NUMPADENTER"]),
+        ],
+    ),
+];
+
+/// What separates the combinations of a sequence unless `--sep` says otherwise
+pub const DEFAULT_SEPARATOR: char = ',';
+
+/// Highest `F<n>` key the parser knows about
+pub const MAX_FUNCTION_KEY: u8 = 24;
+
+/// Keys to hold plus the main key of one `+`-joined combination. The held
+/// keys are the modifiers followed by any earlier keys of a chord like `A+D`
+pub type Combo = (Vec<Key>, Option<Key>);
+
+/// How to resolve tokens that aren't plain built-in key names. The default
+/// is what the command line uses without any parsing flags.
+pub struct ParseOptions {
+    /// Reject unknown tokens instead of guessing a character
+    pub strict: bool,
+    /// Hold Shift for uppercase letters and shifted symbols
+    pub auto_shift: bool,
+    /// Allow several regular keys in one combination, holding all but the last
+    pub chord: bool,
+    /// User-defined names (upper-cased) and the combination each expands to
+    pub aliases: HashMap<String, String>,
+    /// Accept the keys that put the machine to sleep or power it off
+    pub allow_power: bool,
+    /// What separates combinations, [`DEFAULT_SEPARATOR`] if None
+    pub separator: Option<char>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            // Like the command line, where --no-auto-shift turns it off
+            auto_shift: true,
+            chord: false,
+            aliases: HashMap::new(),
+            allow_power: false,
+            separator: None,
+        }
+    }
+}
+
+/// Parse the `+`-separated tokens of one combination. Aliases are resolved
+/// first; unknown tokens fall back to their first character unless
+/// `options.strict` is set
+///
+/// ```
+/// use enigo::Key;
+/// use keystroke::{parse_key_parts, ParseOptions};
+///
+/// let options = ParseOptions::default();
+/// let (modifiers, key) = parse_key_parts(&["CTRL", "SHIFT", "S"], &options).unwrap();
+/// assert_eq!(modifiers, vec![Key::LControl, Key::LShift]);
+/// assert_eq!(key, Some(Key::Unicode('s')));
+///
+/// let strict = ParseOptions {
+///     strict: true,
+///     ..ParseOptions::default()
+/// };
+/// assert!(parse_key_parts(&["CTRL", "SHFT"], &strict).is_err());
+/// ```
+pub fn parse_key_parts(parts: &[&str], options: &ParseOptions) -> Result<Combo, String> {
+    let combo = parts.join("+");
+    let mut modifiers = Vec::new();
+    // Non-modifier keys in order; the last is the main key and any earlier
+    // ones are held with the modifiers
+    let mut chord = Vec::new();
+    // The tokens they were written as
+    let mut chord_tokens = Vec::new();
+    let mut needs_shift = false;
+
+    // Never a character to fall back to, only a stray separator
+    if combo.is_empty() {
+        return Err("Empty key combination, check for a stray separator".to_string());
+    }
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("Empty key in '{}', check for a stray '+'", combo));
+    }
+
+    let mut parts = parts.iter().peekable();
+    while let Some(part) = parts.next() {
+        let upper = part.to_uppercase();
+        let chord_len = chord.len();
+
+        // `U+00E9` arrives split in two by the `+` separator
+        if upper == "U" {
+            if let Some(hex) = parts.next_if(|next| is_codepoint_hex(next, options)) {
+                chord.push(codepoint(hex, &format!("U+{}", hex))?);
+                chord_tokens.push(format!("U+{}", hex));
+                continue;
+            }
+        }
+
+        if let Some(combo) = options.aliases.get(&upper) {
+            // Aliases expand to built-in keys only, so they can't recurse
+            let expansion: Vec<&str> = combo.split('+').collect();
+            // An alias that is a chord counts as one key
+            let builtin = ParseOptions {
+                strict: true,
+                auto_shift: options.auto_shift,
+                chord: true,
+                allow_power: options.allow_power,
+                ..ParseOptions::default()
+            };
+            let (alias_modifiers, alias_key) = parse_key_parts(&expansion, &builtin)
+                .map_err(|e| format!("Alias '{}': {}", upper, e))?;
+            modifiers.extend(alias_modifiers);
+            if let Some(key) = alias_key {
+                chord.push(key);
+                chord_tokens.push(upper);
+            }
+            continue;
+        }
+
+        match upper.as_str() {
+            // Function keys
+            s if is_function_key(s) => chord.push(function_key(s)?),
+
+            // Codepoint escape, e.g. `\u{E9}`
+            s if s.starts_with("\\U{") && s.ends_with('}') => {
+                chord.push(codepoint(&part[3..part.len() - 1], part)?)
+            }
+
+            s => match lookup(s) {
+                Some(KeyToken::Modifier(key)) => {
+                    if !chord.is_empty() {
+                        eprintln!(
+                            "Warning: Modifier '{}' comes after the main key in '{}', \
+                             it is pressed first anyway",
+                            s, combo
+                        );
+                    }
+                    modifiers.push(key)
+                }
+                Some(KeyToken::Main(key)) => chord.push(key),
+                Some(KeyToken::Power(key)) if options.allow_power => chord.push(key),
+                Some(KeyToken::Power(_)) => {
+                    return Err(format!(
+                        "Key '{}' can put the machine to sleep or turn it off, \
+                         use --allow-power to send it",
+                        s
+                    ))
+                }
+                Some(KeyToken::Unsupported) => {
+                    return Err(format!(
+                        "Key '{}' is not supported on {}",
+                        s,
+                        std::env::consts::OS
+                    ))
+                }
+
+                None if s.starts_with("NUMPAD") => {
+                    return Err(format!("Unknown numpad key '{}'", s))
+                }
+
+                // Single character (letter, number or symbol)
+                None if s.len() == 1 => {
+                    let c = part.chars().next().unwrap();
+                    match shifted_base(c) {
+                        Some(base) if options.auto_shift => {
+                            needs_shift = true;
+                            chord.push(Key::Unicode(base));
+                        }
+                        _ => chord.push(Key::Unicode(c.to_ascii_lowercase())),
+                    }
+                }
+
+                None if options.strict => return Err(format!("Unknown key '{}'", s)),
+
+                // Unknown key - try as unicode
+                None => {
+                    eprintln!("Warning: Unknown key '{}', treating as unicode", s);
+                    if let Some(c) = s.chars().next() {
+                        chord.push(Key::Unicode(c.to_ascii_lowercase()));
+                    }
+                }
+            },
+        }
+        if chord.len() > chord_len {
+            chord_tokens.push(upper);
+        }
+    }
+
+    if chord.len() > 1 && !options.chord {
+        return Err(format!(
+            "'{}' has more than one main key ({}), use --chord to hold all but the last",
+            combo,
+            chord_tokens.join(", ")
+        ));
+    }
+
+    if needs_shift
+        && !modifiers
+            .iter()
+            .any(|m| matches!(m, Key::LShift | Key::RShift))
+    {
+        modifiers.push(Key::LShift);
+    }
+
+    let main_key = chord.pop();
+    modifiers.extend(chord);
+    Ok((modifiers, main_key))
+}
+
+/// The unshifted key for an uppercase letter or a symbol typed with Shift
+/// on a US layout
+fn shifted_base(c: char) -> Option<char> {
+    let base = match c {
+        'A'..='Z' => c.to_ascii_lowercase(),
+        '!' => '1',
+        '@' => '2',
+        '#' => '3',
+        '$' => '4',
+        '%' => '5',
+        '^' => '6',
+        '&' => '7',
+        '*' => '8',
+        '(' => '9',
+        ')' => '0',
+        '_' => '-',
+        ':' => ';',
+        '"' => '\'',
+        '<' => ',',
+        '>' => '.',
+        '?' => '/',
+        '{' => '[',
+        '}' => ']',
+        '|' => '\\',
+        '~' => '`',
+        _ => return None,
+    };
+    Some(base)
+}
+
+/// Whether the token after a lone `U` is the hex half of `U+XXXX` rather
+/// than another key of the combination
+fn is_codepoint_hex(token: &str, options: &ParseOptions) -> bool {
+    let upper = token.to_uppercase();
+    (4..=6).contains(&token.len())
+        && token.bytes().all(|b| b.is_ascii_alphanumeric())
+        && lookup(&upper).is_none()
+        && !is_function_key(&upper)
+        && !options.aliases.contains_key(&upper)
+}
+
+/// The character with the given hexadecimal codepoint
+fn codepoint(hex: &str, token: &str) -> Result<Key, String> {
+    if hex.is_empty() || hex.len() > 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Invalid codepoint '{}': expected 1 to 6 hex digits",
+            token
+        ));
+    }
+    let value = u32::from_str_radix(hex, 16).expect("validated hex digits");
+    char::from_u32(value).map(Key::Unicode).ok_or(format!(
+        "Invalid codepoint '{}': not a Unicode scalar value",
+        token
+    ))
+}
+
+/// Split a sequence into its combinations at `separator`. A separator
+/// preceded by a backslash is kept as the key it names, so `CTRL+\,` is
+/// Ctrl+comma rather than Ctrl followed by an empty combination.
+pub fn split_sequence(sequence: &str, separator: char) -> Vec<String> {
+    let mut combos = vec![String::new()];
+    let mut chars = sequence.chars().peekable();
+    while let Some(c) = chars.next() {
+        let current = combos.last_mut().unwrap();
+        match c {
+            '\\' if chars.peek() == Some(&separator) => current.push(chars.next().unwrap()),
+            c if c == separator => combos.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    combos
+}
+
+/// Find a named key in the table
+fn lookup(name: &str) -> Option<KeyToken> {
+    KEY_GROUPS
+        .iter()
+        .flat_map(|(_, keys)| keys.iter())
+        .find(|key| key.names.contains(&name))
+        .map(|key| key.token)
+}
+
+/// Whether a token has the `F<n>` shape of a function key
+fn is_function_key(name: &str) -> bool {
+    name.len() > 1 && name.starts_with('F') && name[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Map an `F<n>` token to its function key
+pub fn function_key(name: &str) -> Result<Key, String> {
+    let key = match &name[1..] {
+        "1" => Key::F1,
+        "2" => Key::F2,
+        "3" => Key::F3,
+        "4" => Key::F4,
+        "5" => Key::F5,
+        "6" => Key::F6,
+        "7" => Key::F7,
+        "8" => Key::F8,
+        "9" => Key::F9,
+        "10" => Key::F10,
+        "11" => Key::F11,
+        "12" => Key::F12,
+        "13" => Key::F13,
+        "14" => Key::F14,
+        "15" => Key::F15,
+        "16" => Key::F16,
+        "17" => Key::F17,
+        "18" => Key::F18,
+        "19" => Key::F19,
+        "20" => Key::F20,
+        #[cfg(not(target_os = "macos"))]
+        "21" => Key::F21,
+        #[cfg(not(target_os = "macos"))]
+        "22" => Key::F22,
+        #[cfg(not(target_os = "macos"))]
+        "23" => Key::F23,
+        #[cfg(not(target_os = "macos"))]
+        "24" => Key::F24,
+        #[cfg(target_os = "macos")]
+        "21" | "22" | "23" | "24" => {
+            return Err(format!(
+                "Key '{}' is not supported on {}",
+                name,
+                std::env::consts::OS
+            ))
+        }
+        _ => return Err(format!("Unknown function key '{}'", name)),
+    };
+    Ok(key)
+}
+
+/// Every modifier key in the table, each listed once
+pub fn modifier_keys() -> Vec<Key> {
+    let mut keys = Vec::new();
+    for named in KEY_GROUPS.iter().flat_map(|(_, keys)| keys.iter()) {
+        if let KeyToken::Modifier(key) = named.token {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+/// Every supported token grouped by category, aliases included
+pub fn supported_tokens() -> Vec<(&'static str, Vec<String>)> {
+    let function_keys = (1..=MAX_FUNCTION_KEY)
+        .map(|n| format!("F{}", n))
+        .filter(|name| function_key(name).is_ok())
+        .collect();
+
+    let mut groups: Vec<_> = KEY_GROUPS
+        .iter()
+        .map(|(category, keys)| {
+            let names = keys
+                .iter()
+                .filter(|key| !matches!(key.token, KeyToken::Unsupported))
+                .flat_map(|key| key.names.iter().map(|name| name.to_string()))
+                .collect();
+            (*category, names)
+        })
+        .collect();
+    groups.push(("Function keys", function_keys));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Options that only resolve names, for tests of the names themselves.
+    /// Auto-shift, on by default, has its own test.
+    fn names_only() -> ParseOptions {
+        ParseOptions {
+            auto_shift: false,
+            ..ParseOptions::default()
+        }
+    }
+
+    fn parse(combo: &str) -> Result<Combo, String> {
+        parse_with(combo, &names_only())
+    }
+
+    fn parse_with(combo: &str, options: &ParseOptions) -> Result<Combo, String> {
+        let parts: Vec<&str> = combo.split('+').collect();
+        parse_key_parts(&parts, options)
+    }
+
+    fn main_key(combo: &str) -> Key {
+        let (modifiers, key) = parse(combo).unwrap();
+        assert!(modifiers.is_empty(), "{} has modifiers", combo);
+        key.unwrap_or_else(|| panic!("{} has no main key", combo))
+    }
+
+    #[test]
+    fn plain_letters_and_digits() {
+        assert_eq!(main_key("W"), Key::Unicode('w'));
+        assert_eq!(main_key("w"), Key::Unicode('w'));
+        assert_eq!(main_key("7"), Key::Unicode('7'));
+    }
+
+    #[test]
+    fn chords() {
+        assert_eq!(
+            parse("CTRL+S").unwrap(),
+            (vec![Key::LControl], Some(Key::Unicode('s')))
+        );
+        assert_eq!(
+            parse("ctrl+shift+F1").unwrap(),
+            (vec![Key::LControl, Key::LShift], Some(Key::F1))
+        );
+        assert_eq!(
+            parse("RCTRL+RSHIFT").unwrap(),
+            (vec![Key::RControl, Key::RShift], None)
+        );
+        assert_eq!(parse("WIN+E").unwrap().0, vec![Key::Meta]);
+        assert_eq!(parse("ALT+TAB").unwrap(), (vec![LEFT_ALT], Some(Key::Tab)));
+    }
+
+    #[test]
+    fn chords_hold_earlier_keys() {
+        let chord = ParseOptions {
+            chord: true,
+            ..names_only()
+        };
+        assert_eq!(
+            parse_with("A+D", &chord).unwrap(),
+            (vec![Key::Unicode('a')], Some(Key::Unicode('d')))
+        );
+        assert_eq!(
+            parse_with("SHIFT+A+D", &chord).unwrap(),
+            (
+                vec![Key::LShift, Key::Unicode('a')],
+                Some(Key::Unicode('d'))
+            )
+        );
+    }
+
+    #[test]
+    fn one_main_key_without_chord() {
+        let error = parse("A+B").unwrap_err();
+        assert!(error.contains("more than one main key (A, B)"), "{}", error);
+        assert!(parse("CTRL+F1+S").is_err());
+        // Modifiers may come last, and are still pressed first
+        assert_eq!(
+            parse("W+CTRL").unwrap(),
+            (vec![Key::LControl], Some(Key::Unicode('w')))
+        );
+    }
+
+    #[test]
+    fn function_keys() {
+        let expected = [
+            Key::F1,
+            Key::F2,
+            Key::F3,
+            Key::F4,
+            Key::F5,
+            Key::F6,
+            Key::F7,
+            Key::F8,
+            Key::F9,
+            Key::F10,
+            Key::F11,
+            Key::F12,
+            Key::F13,
+            Key::F14,
+            Key::F15,
+            Key::F16,
+            Key::F17,
+            Key::F18,
+            Key::F19,
+            Key::F20,
+        ];
+        for (n, key) in (1..).zip(expected) {
+            assert_eq!(main_key(&format!("F{}", n)), key);
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        for (n, key) in [
+            (21, Key::F21),
+            (22, Key::F22),
+            (23, Key::F23),
+            (24, Key::F24),
+        ] {
+            assert_eq!(main_key(&format!("F{}", n)), key);
+        }
+        #[cfg(target_os = "macos")]
+        assert!(parse("F21").unwrap_err().contains("not supported"));
+
+        assert!(parse("F25").unwrap_err().contains("Unknown function key"));
+        assert!(parse("F0").is_err());
+    }
+
+    #[test]
+    fn arrows() {
+        assert_eq!(main_key("UP"), Key::UpArrow);
+        assert_eq!(main_key("DOWN"), Key::DownArrow);
+        assert_eq!(main_key("LEFT"), Key::LeftArrow);
+        assert_eq!(main_key("RIGHT"), Key::RightArrow);
+        assert_eq!(main_key("ARROWUP"), Key::UpArrow);
+    }
+
+    #[test]
+    fn numpad() {
+        let digits = [
+            Key::Numpad0,
+            Key::Numpad1,
+            Key::Numpad2,
+            Key::Numpad3,
+            Key::Numpad4,
+            Key::Numpad5,
+            Key::Numpad6,
+            Key::Numpad7,
+            Key::Numpad8,
+            Key::Numpad9,
+        ];
+        for (n, key) in digits.into_iter().enumerate() {
+            assert_eq!(main_key(&format!("NUMPAD{}", n)), key);
+        }
+        assert_eq!(main_key("NUMPADADD"), Key::Add);
+        assert_eq!(main_key("NUMPADSUBTRACT"), Key::Subtract);
+        assert_eq!(main_key("NUMPADMULTIPLY"), Key::Multiply);
+        assert_eq!(main_key("NUMPADDIVIDE"), Key::Divide);
+        assert_eq!(main_key("NUMPADDECIMAL"), Key::Decimal);
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        assert_eq!(main_key("NUMPADENTER"), Key::Other(0xff8d));
+        #[cfg(target_os = "windows")]
+        assert!(parse("NUMPADENTER")
+            .unwrap_err()
+            .contains("not supported on windows"));
+
+        assert!(parse("NUMPAD10")
+            .unwrap_err()
+            .contains("Unknown numpad key"));
+    }
+
+    #[test]
+    fn special_keys_and_aliases() {
+        assert_eq!(main_key("ENTER"), Key::Return);
+        assert_eq!(main_key("RETURN"), Key::Return);
+        assert_eq!(main_key("ESC"), Key::Escape);
+        assert_eq!(main_key("PGDN"), Key::PageDown);
+        assert_eq!(main_key("COMMA"), Key::Unicode(','));
+        assert_eq!(main_key("CAPSLOCK"), Key::CapsLock);
+        assert_eq!(main_key("CAPS"), Key::CapsLock);
+        assert_eq!(main_key("SPACEBAR"), Key::Space);
+        assert_eq!(main_key("BKSP"), Key::Backspace);
+        assert_eq!(main_key("CR"), Key::Return);
+        #[cfg(not(target_os = "macos"))]
+        assert_eq!(main_key("CONTEXTMENU"), main_key("APPS"));
+
+        let (modifiers, key) = parse("CMD+OPT+S").unwrap();
+        assert_eq!(modifiers, vec![Key::Meta, LEFT_ALT]);
+        assert_eq!(key, Some(Key::Unicode('s')));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn unsupported_keys() {
+        assert!(parse("INSERT").unwrap_err().contains("not supported"));
+    }
+
+    #[test]
+    fn power_keys_need_allow_power() {
+        assert!(parse("POWER").unwrap_err().contains("--allow-power"));
+        let allowed = ParseOptions {
+            allow_power: true,
+            ..names_only()
+        };
+        #[cfg(not(target_os = "windows"))]
+        assert!(parse_with("POWER", &allowed).is_ok());
+        #[cfg(target_os = "windows")]
+        assert!(parse_with("POWER", &allowed)
+            .unwrap_err()
+            .contains("not supported"));
+    }
+
+    #[test]
+    fn escaped_separators() {
+        assert_eq!(split_sequence("CTRL+S,ENTER", ','), ["CTRL+S", "ENTER"]);
+        assert_eq!(split_sequence("CTRL+\\,", ','), ["CTRL+,"]);
+        assert_eq!(split_sequence("\\,,A", ','), [",", "A"]);
+        assert_eq!(split_sequence("CTRL+,;A", ';'), ["CTRL+,", "A"]);
+        // Other escapes are left for the key parser
+        assert_eq!(split_sequence("\\u{E9},A", ','), ["\\u{E9}", "A"]);
+
+        let (modifiers, key) = parse("CTRL+,").unwrap();
+        assert_eq!(modifiers, vec![Key::LControl]);
+        assert_eq!(key, Some(Key::Unicode(',')));
+    }
+
+    #[test]
+    fn empty_keys_are_errors() {
+        assert!(parse("CTRL+").unwrap_err().contains("Empty key in 'CTRL+'"));
+        assert!(parse("+A").unwrap_err().contains("Empty key"));
+        let options = names_only();
+        for sequence in ["A,", ",A", "A,,B", ""] {
+            assert!(
+                crate::parse_combos(sequence, &options)
+                    .unwrap_err()
+                    .contains("Empty key combination"),
+                "{:?} parsed",
+                sequence
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_token_falls_back_to_first_character() {
+        assert_eq!(main_key("QQ"), Key::Unicode('q'));
+    }
+
+    #[test]
+    fn strict_rejects_unknown_tokens() {
+        let strict = ParseOptions {
+            strict: true,
+            ..names_only()
+        };
+        assert_eq!(
+            parse_with("CTRL+QQ", &strict).unwrap_err(),
+            "Unknown key 'QQ'"
+        );
+        assert!(parse_with("CTRL+Q", &strict).is_ok());
+    }
+
+    #[test]
+    fn auto_shift() {
+        // On by default, like the command line
+        let options = ParseOptions::default();
+        assert_eq!(
+            parse_with("A", &options).unwrap(),
+            (vec![Key::LShift], Some(Key::Unicode('a')))
+        );
+        assert_eq!(
+            parse_with("!", &options).unwrap(),
+            (vec![Key::LShift], Some(Key::Unicode('1')))
+        );
+        assert_eq!(
+            parse_with("SHIFT+A", &options).unwrap(),
+            (vec![Key::LShift], Some(Key::Unicode('a')))
+        );
+        assert_eq!(
+            parse_with("a", &options).unwrap(),
+            (vec![], Some(Key::Unicode('a')))
+        );
+    }
+
+    #[test]
+    fn codepoints() {
+        assert_eq!(main_key("U+00E9"), Key::Unicode('é'));
+        assert_eq!(main_key("\\u{E9}"), Key::Unicode('é'));
+        assert_eq!(
+            parse("CTRL+U+00C9").unwrap(),
+            (vec![Key::LControl], Some(Key::Unicode('É')))
+        );
+        assert_eq!(
+            parse("CTRL+U").unwrap(),
+            (vec![Key::LControl], Some(Key::Unicode('u')))
+        );
+        assert!(parse("U+D800")
+            .unwrap_err()
+            .contains("not a Unicode scalar value"));
+        assert!(parse("\\u{XYZ}").unwrap_err().contains("hex digits"));
+    }
+
+    #[test]
+    fn user_aliases() {
+        let options = ParseOptions {
+            aliases: HashMap::from([
+                ("HORN".to_string(), "H".to_string()),
+                ("STARTUP".to_string(), "CTRL+SHIFT+E".to_string()),
+            ]),
+            ..names_only()
+        };
+        assert_eq!(
+            parse_with("horn", &options).unwrap(),
+            (vec![], Some(Key::Unicode('h')))
+        );
+        assert_eq!(
+            parse_with("ALT+STARTUP", &options).unwrap(),
+            (
+                vec![LEFT_ALT, Key::LControl, Key::LShift],
+                Some(Key::Unicode('e'))
+            )
+        );
+    }
+}
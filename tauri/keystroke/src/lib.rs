@@ -0,0 +1,1615 @@
+//! Keyboard and mouse input simulation shared by the `keystroke` CLI.
+//!
+//! Commands are described by [`KeyCommand`] and run on an [`Enigo`] instance
+//! with [`execute`]. Key combinations use the same syntax as the command
+//! line and are resolved by [`parse_key_parts`].
+//!
+//! ```no_run
+//! use enigo::{Enigo, Settings};
+//! use keystroke::{execute, Edge, KeyArgs, KeyCommand, ParseArgs};
+//!
+//! let mut enigo = Enigo::new(&Settings::default()).unwrap();
+//! let save = KeyCommand::Tap {
+//!     keys: KeyArgs::new("CTRL+S".to_string(), ParseArgs::default()),
+//!     hold: None,
+//!     repeat: 1,
+//!     repeat_interval: 0,
+//!     reset_modifiers: false,
+//!     edge: Edge::Press,
+//! };
+//! execute(&mut enigo, save).unwrap();
+//! ```
+
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod autorepeat;
+mod config;
+mod doctor;
+pub mod keys;
+mod numlock;
+mod permissions;
+mod record;
+mod self_test;
+pub mod server;
+mod timeline;
+mod verify;
+pub mod window;
+
+pub use doctor::{doctor, wayland_warning};
+pub use keys::{parse_key_parts, Combo, ParseOptions};
+pub use permissions::check_permissions;
+pub use record::record;
+pub use self_test::self_test;
+
+/// Help text shared by every command that sends keys
+pub const LOCK_KEYS_HELP: &str =
+    "Lock keys (CAPSLOCK, NUMLOCK, SCROLLLOCK) are stateful: `tap` toggles the lock, \
+     while `down` and `up` send a physical press and release.";
+
+const AUTOREPEAT_HELP: &str =
+    "Unlike `down --hold` or `tap --hold`, which send one press and one release, autorepeat \
+     makes the key repeat while it is held, as holding it on a keyboard does: the target sees \
+     further key-down events after the repeat delay. Use it for bindings that act on every \
+     repeat, such as a throttle that keeps moving while its key is held. On X11 the server \
+     repeats the key, if key repeat is on; on Windows the repeats follow the keyboard \
+     settings; on macOS they come after 500ms, every 33ms.";
+
+/// A single command line received in daemon mode
+#[derive(Parser)]
+#[command(name = "keystroke", no_binary_name = true)]
+pub(crate) struct DaemonCommand {
+    #[command(subcommand)]
+    command: KeyCommand,
+}
+
+/// A single input command, as given on the command line
+#[derive(Subcommand)]
+pub enum KeyCommand {
+    /// Press key down (hold)
+    #[command(after_help = LOCK_KEYS_HELP)]
+    Down {
+        #[command(flatten)]
+        keys: KeyArgs,
+
+        /// Release the key again after holding it for this many milliseconds
+        #[arg(long)]
+        hold: Option<u64>,
+    },
+    /// Release key
+    #[command(after_help = LOCK_KEYS_HELP)]
+    Up {
+        #[command(flatten)]
+        keys: KeyArgs,
+    },
+    /// Press and release key
+    #[command(after_help = LOCK_KEYS_HELP)]
+    Tap {
+        #[command(flatten)]
+        keys: KeyArgs,
+
+        /// Hold the key for this many milliseconds before releasing it
+        #[arg(long)]
+        hold: Option<u64>,
+
+        /// Number of times to tap the main key while holding the modifiers
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        repeat: u32,
+
+        /// Delay between repeated taps in milliseconds
+        #[arg(long, default_value_t = 0)]
+        repeat_interval: u64,
+
+        /// Release Ctrl, Shift, Alt and Meta first, in case one is still down
+        /// from an earlier command and would turn the tap into a shortcut
+        #[arg(long)]
+        reset_modifiers: bool,
+
+        /// Which transition of the main key the target should act on: press
+        /// sends it as one click, release holds it briefly first so the up
+        /// transition arrives as a separate event
+        #[arg(long, value_enum, default_value_t = Edge::Press)]
+        edge: Edge,
+    },
+    /// Hold a key and let it auto-repeat, then release it
+    #[command(after_help = AUTOREPEAT_HELP)]
+    Autorepeat {
+        /// Key combination to hold; only the last key repeats (e.g., "W" or "SHIFT+UP")
+        key: String,
+
+        /// How long to hold the key in milliseconds
+        #[arg(long, value_name = "MS")]
+        duration: u64,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Simulate a mouse button
+    Mouse {
+        /// Action to perform
+        #[arg(value_enum)]
+        action: Action,
+
+        /// Mouse button (LEFT, RIGHT, MIDDLE)
+        button: String,
+    },
+    /// Move the mouse cursor
+    #[command(allow_negative_numbers = true)]
+    #[command(group(ArgGroup::new("coordinate").required(true).args(["rel", "abs"])))]
+    MouseMove {
+        /// Move relative to the current cursor position
+        #[arg(long)]
+        rel: bool,
+
+        /// Move to an absolute position on the primary display
+        #[arg(long)]
+        abs: bool,
+
+        /// Horizontal offset (or x coordinate with --abs) in pixels
+        x: i32,
+
+        /// Vertical offset (or y coordinate with --abs) in pixels
+        y: i32,
+    },
+    /// Scroll the mouse wheel
+    #[command(allow_negative_numbers = true)]
+    Scroll {
+        /// Scroll axis
+        #[arg(value_enum, ignore_case = true)]
+        axis: ScrollAxis,
+
+        /// Number of wheel steps, or pixels with `--unit pixels` (negative
+        /// scrolls up/left)
+        amount: i32,
+
+        /// What the amount counts
+        #[arg(long, value_enum, default_value_t = ScrollUnit::Lines)]
+        unit: ScrollUnit,
+    },
+    /// Release every modifier, plus any extra keys, to recover from stuck input
+    ReleaseAll {
+        /// Additional keys to release, in the same syntax as `up` (e.g., "W,A,S,D")
+        #[arg(long)]
+        keys: Option<String>,
+
+        /// TOML file of key aliases
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+    /// List every supported key name
+    ListKeys {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Show how a key combination is parsed, without sending anything
+    Resolve {
+        /// Key combination, in the same syntax as `tap`
+        key: String,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// List the keys and mouse buttons currently held down, as JSON (daemon
+    /// and socket mode only)
+    Status,
+    /// Check that the OS allows sending input (the Accessibility permission
+    /// on macOS), and explain how to grant it if not
+    CheckPermissions {
+        /// Open the settings pane of each missing permission
+        #[arg(long)]
+        open: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Report the platform and input backend, and test that input works
+    ///
+    /// In a Wayland session this also checks whether the uinput route is
+    /// usable through ydotool. keystroke can't send through uinput itself,
+    /// since enigo has no backend for it, so there is no `--backend uinput`.
+    Doctor {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Send a short, harmless key sequence and report whether each step
+    /// went through; doctor inspects the environment, this exercises it
+    Test {
+        /// Also type a line of text into the focused field and read it back
+        /// through the clipboard (see `type --verify`)
+        #[arg(long = "type")]
+        type_text: bool,
+
+        /// With --type, seconds to wait first so a text field can be focused
+        #[arg(long, value_name = "SECS", default_value_t = 3)]
+        countdown: u64,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Play back a JSON timeline of steps
+    Play {
+        /// Timeline file
+        file: PathBuf,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Record keys pressed by hand into a timeline for `play`, until the
+    /// stop key is pressed (X11 and Windows)
+    Record {
+        /// Timeline file to write
+        file: PathBuf,
+
+        /// Key that ends the recording, which isn't recorded itself
+        #[arg(long, value_name = "KEY", default_value = "ESCAPE")]
+        stop: String,
+    },
+    /// Run an inline sequence of typed text and key taps, e.g.
+    /// 'text:"1234",key:ENTER,key:CTRL+S'
+    Run {
+        /// Comma-separated steps, each `text:` followed by the text (quoted
+        /// if it contains commas) or `key:` followed by a key combination
+        sequence: String,
+
+        /// Delay between steps in milliseconds [default: KEYSTROKE_DELAY_MS, or none]
+        #[arg(long)]
+        delay: Option<u64>,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Type a string of text
+    Type {
+        /// Text to type, or "-" to read it from stdin (not in daemon mode)
+        text: String,
+
+        /// Delay between characters in milliseconds
+        #[arg(long)]
+        delay: Option<u64>,
+
+        /// Type newlines as characters instead of pressing Return
+        #[arg(long)]
+        literal: bool,
+
+        /// Keys to hold while typing, e.g. "SHIFT" or "CTRL+ALT". The text is
+        /// then sent as key taps, so the held keys apply to every character
+        /// as on a physical keyboard
+        #[arg(long, value_name = "KEYS")]
+        with: Option<String>,
+
+        /// Afterwards, select and copy everything in the focused field and
+        /// fail if it isn't the text, to tell whether input was dropped. Best
+        /// effort: needs an empty field that supports select-all and copy,
+        /// and overwrites the selection
+        #[arg(long)]
+        verify: bool,
+    },
+}
+
+/// The keys a command sends and how they are paced
+#[derive(Args)]
+pub struct KeyArgs {
+    /// Key combination (e.g., "W", "CTRL+S", "SHIFT+F1", or "A+D" with --chord to hold both), or several
+    /// comma-separated combinations to send in order (e.g., "CTRL+S,ENTER"). Write a comma key as "\,"
+    /// or "COMMA"
+    pub key: String,
+
+    /// Delay between comma-separated combinations in milliseconds
+    /// [default: KEYSTROKE_DELAY_MS, or none]
+    #[arg(long)]
+    pub delay: Option<u64>,
+
+    /// Delay between the modifiers and the main key in milliseconds, applied
+    /// both after pressing and before releasing the modifiers
+    /// [default: KEYSTROKE_DELAY_MS, or 0]
+    #[arg(long)]
+    pub mod_delay: Option<u64>,
+
+    /// Read the keys as raw platform keycodes instead of names, e.g. "17" or
+    /// "42+17". These address physical key positions whatever the layout:
+    /// scan codes on Windows (add 0xFF00 for extended keys), X11 keycodes on
+    /// Linux and virtual keycodes on macOS. Decimal or 0x-prefixed hex
+    #[arg(long)]
+    pub raw: bool,
+
+    /// Turn NumLock on or off first if it isn't already, so numpad keys send
+    /// digits (on) or navigation keys (off) whatever state it was left in
+    #[arg(long, value_enum, value_name = "STATE")]
+    pub numlock: Option<LockState>,
+
+    /// Put NumLock back the way it was once the keys are sent
+    #[arg(long, requires = "numlock")]
+    pub restore: bool,
+
+    /// Order to let go of a combination in: reverse releases the main key and
+    /// then the modifiers last to first, natural releases the modifiers in the
+    /// order they were pressed and the main key last
+    #[arg(long, value_enum, default_value_t = ReleaseOrder::Reverse)]
+    pub release_order: ReleaseOrder,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+impl KeyArgs {
+    /// Keys for a single command with no delays
+    pub fn new(key: String, parse: ParseArgs) -> Self {
+        Self {
+            key,
+            delay: None,
+            mod_delay: None,
+            raw: false,
+            numlock: None,
+            restore: false,
+            release_order: ReleaseOrder::Reverse,
+            parse,
+        }
+    }
+}
+
+/// How key names are resolved
+#[derive(Args, Clone, Default)]
+pub struct ParseArgs {
+    /// Reject unrecognized key names instead of guessing a character
+    #[arg(long)]
+    pub strict: bool,
+
+    /// TOML file of key aliases, resolved before the built-in key names
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Send uppercase letters and shifted symbols without adding Shift
+    #[arg(long)]
+    pub no_auto_shift: bool,
+
+    /// Allow several regular keys in a combination, e.g. "A+D", holding all
+    /// but the last with the modifiers
+    #[arg(long)]
+    pub chord: bool,
+
+    /// Accept SLEEP and POWER, which are refused otherwise so a stray
+    /// binding can't put the machine to sleep or turn it off
+    #[arg(long)]
+    pub allow_power: bool,
+
+    /// Separate combinations with this character instead of a comma
+    #[arg(long, value_name = "CHAR")]
+    pub sep: Option<char>,
+}
+
+impl ParseArgs {
+    /// Resolve these arguments, loading the alias file if one was given
+    pub fn options(&self) -> Result<ParseOptions, String> {
+        let aliases = match &self.config {
+            Some(path) => config::load_aliases(path)?,
+            None => Default::default(),
+        };
+        Ok(ParseOptions {
+            strict: self.strict,
+            auto_shift: !self.no_auto_shift,
+            chord: self.chord,
+            aliases,
+            allow_power: self.allow_power,
+            separator: Some(self.separator()?),
+        })
+    }
+
+    /// The separator given with `--sep`, or the default one
+    pub fn separator(&self) -> Result<char, String> {
+        match self.sep {
+            Some(c) if c == '+' || c == '\\' || c.is_whitespace() => Err(format!(
+                "--sep can't be '{}', it is part of the key syntax",
+                c
+            )),
+            Some(c) => Ok(c),
+            None => Ok(keys::DEFAULT_SEPARATOR),
+        }
+    }
+}
+
+/// Unit of a scroll amount
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollUnit {
+    /// Wheel steps, one notch each: 120 wheel units on Windows, one button
+    /// 4/5 (6/7) click on Linux and one line event on macOS
+    #[default]
+    Lines,
+    /// Pixels, for smooth scrolling (macOS only; other platforms scroll
+    /// lines instead)
+    Pixels,
+}
+
+/// Output format of informational commands
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// JSON
+    Json,
+}
+
+/// Direction of a scroll command
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollAxis {
+    /// Scroll up/down
+    Vertical,
+    /// Scroll left/right
+    Horizontal,
+}
+
+/// What to do with a key or mouse button
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Action {
+    /// Press key down (hold)
+    Down,
+    /// Release key
+    Up,
+    /// Press and release key
+    Tap,
+}
+
+/// State of a lock key
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum LockState {
+    On,
+    Off,
+}
+
+/// Transition of a tapped key that the target application acts on
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum Edge {
+    /// Act on key down: press and release at once
+    #[default]
+    Press,
+    /// Act on key up: hold the key for 15ms before releasing it
+    Release,
+}
+
+/// Order the keys of a combination are released in
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReleaseOrder {
+    /// Main key first, then the modifiers in reverse, like nested brackets
+    #[default]
+    Reverse,
+    /// Modifiers in the order they were pressed, then the main key, for
+    /// targets that only act on a key released on its own
+    Natural,
+}
+
+/// How long `--edge release` holds the main key, just long enough for the
+/// target to register the press and the release as separate transitions.
+/// The `Edge::Release` help text gives the same duration.
+const RELEASE_EDGE_HOLD: Duration = Duration::from_millis(15);
+
+/// Exit status for invalid input, including clap usage errors
+pub const EXIT_PARSE_ERROR: i32 = 10;
+/// Exit status for input events the operating system rejected
+pub const EXIT_SEND_ERROR: i32 = 20;
+/// Exit status for a permission the OS requires to send input not being granted
+pub const EXIT_PERMISSION_ERROR: i32 = 30;
+/// Exit status for the window given with `--window` not existing
+pub const EXIT_NOT_FOUND: i32 = 40;
+
+/// The exit statuses, for the end of `--help`. These are stable, so callers
+/// can act on them.
+pub const EXIT_CODES_HELP: &str = "\
+Exit status:
+  0   Success
+  10  Invalid command line, key name or input file; nothing was sent
+  20  The operating system rejected an input event, or input could not be set up
+  30  A permission required to send input is not granted (check-permissions)
+  40  The window given with --window doesn't exist";
+
+/// Times a rejected key or mouse button event is retried by default
+pub const DEFAULT_SEND_RETRIES: u32 = 2;
+const SEND_RETRY_DELAY: Duration = Duration::from_millis(5);
+static SEND_RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_SEND_RETRIES);
+
+/// Environment variable with a delay in milliseconds used between steps of a
+/// sequence and between modifiers and keys when no delay flag is given
+const DELAY_ENV: &str = "KEYSTROKE_DELAY_MS";
+
+/// The delay set through DELAY_ENV, if any
+fn default_delay() -> Option<u64> {
+    let value = std::env::var(DELAY_ENV).ok()?;
+    match value.trim().parse() {
+        Ok(delay) => Some(delay),
+        Err(_) => {
+            eprintln!("Warning: Ignoring invalid {} '{}'", DELAY_ENV, value);
+            None
+        }
+    }
+}
+
+/// Environment variable that, set to anything but 0 or false, refuses mouse
+/// commands like `--no-mouse`
+pub const NO_MOUSE_ENV: &str = "KEYSTROKE_NO_MOUSE";
+static MOUSE_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Refuse mouse commands from now on, for environments that only allow
+/// synthetic keyboard input
+pub fn disable_mouse() {
+    MOUSE_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether NO_MOUSE_ENV asks for mouse commands to be refused
+pub fn mouse_disabled_by_env() -> bool {
+    std::env::var(NO_MOUSE_ENV)
+        .is_ok_and(|value| !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false"))
+}
+
+/// Fail if mouse commands are turned off
+fn check_mouse_allowed(action: &str) -> Result<(), Error> {
+    if MOUSE_DISABLED.load(Ordering::Relaxed) {
+        return Err(Error::Parse(format!(
+            "{} is refused, mouse input is disabled with --no-mouse or {}",
+            action, NO_MOUSE_ENV
+        )));
+    }
+    Ok(())
+}
+
+/// Keys and buttons pressed and not released yet, with when they went down
+pub(crate) static HELD: Mutex<Vec<(Held, Instant)>> = Mutex::new(Vec::new());
+
+/// Something that can be left pressed down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Held {
+    Key(Key),
+    Button(Button),
+    Raw(u16),
+}
+
+impl Held {
+    /// Release it again
+    pub(crate) fn release(self, enigo: &mut Enigo) -> Result<(), Error> {
+        match self {
+            Held::Key(key) => send_key(enigo, key, Direction::Release),
+            Held::Button(button) => send_button(enigo, button, Direction::Release),
+            Held::Raw(code) => RawKey(code).send(enigo, Direction::Release),
+        }
+    }
+}
+
+/// Record a press or release of `input` in `HELD`
+fn track(input: Held, direction: Direction) {
+    if let Ok(mut held) = HELD.lock() {
+        match direction {
+            Direction::Press if !held.iter().any(|(h, _)| *h == input) => {
+                held.push((input, Instant::now()))
+            }
+            Direction::Release => held.retain(|(h, _)| *h != input),
+            _ => {}
+        }
+    }
+}
+
+/// Everything currently held down, as answered to `status`: each entry has
+/// a `key`, `button` or raw `keycode`, and how long it has been held
+pub(crate) fn held_status() -> serde_json::Value {
+    let held = HELD.lock().map(|held| held.clone()).unwrap_or_default();
+    let held: Vec<_> = held
+        .into_iter()
+        .map(|(input, pressed_at)| {
+            let held_ms = pressed_at.elapsed().as_millis() as u64;
+            match input {
+                Held::Key(key) => {
+                    serde_json::json!({ "key": format!("{:?}", key), "held_ms": held_ms })
+                }
+                Held::Button(button) => {
+                    serde_json::json!({ "button": format!("{:?}", button), "held_ms": held_ms })
+                }
+                Held::Raw(code) => serde_json::json!({ "keycode": code, "held_ms": held_ms }),
+            }
+        })
+        .collect();
+    serde_json::json!({ "held": held })
+}
+
+/// Set how many times a key or mouse button event the operating system
+/// rejects, e.g. during a focus change, is retried before the command fails
+pub fn set_send_retries(retries: u32) {
+    SEND_RETRIES.store(retries, Ordering::Relaxed);
+}
+
+/// Why a command failed; each category exits with its own status code so
+/// callers can tell bad input apart from input that could not be delivered
+#[derive(Debug)]
+pub enum Error {
+    /// The command line or its key names were invalid; nothing was sent
+    Parse(String),
+    /// The operating system rejected an input event
+    Send(String),
+    /// The OS doesn't allow this process to send input
+    Permission(String),
+    /// The window the input is meant for doesn't exist
+    NotFound(String),
+}
+
+impl Error {
+    /// Process exit status for this failure
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Parse(_) => EXIT_PARSE_ERROR,
+            Error::Send(_) => EXIT_SEND_ERROR,
+            Error::Permission(_) => EXIT_PERMISSION_ERROR,
+            Error::NotFound(_) => EXIT_NOT_FOUND,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(message)
+            | Error::Send(message)
+            | Error::Permission(message)
+            | Error::NotFound(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Parse(message)
+    }
+}
+
+/// Check the key names of a strict command (`--strict` or `release-all`)
+/// without sending anything
+pub fn validate_strict(command: &KeyCommand) -> Result<(), Error> {
+    match command {
+        KeyCommand::Down { keys, .. } | KeyCommand::Up { keys } | KeyCommand::Tap { keys, .. }
+            if keys.raw =>
+        {
+            parse_raw_combos(&keys.key, keys.parse.separator()?)?;
+        }
+        KeyCommand::Down { keys, .. } | KeyCommand::Up { keys } | KeyCommand::Tap { keys, .. }
+            if keys.parse.strict =>
+        {
+            parse_combos(&keys.key, &keys.parse.options()?)?;
+        }
+        KeyCommand::ReleaseAll {
+            keys: Some(keys),
+            config,
+        } => {
+            parse_combos(keys, &release_all_options(config.clone())?)?;
+        }
+        KeyCommand::Autorepeat { key, parse, .. } if parse.strict => {
+            parse_combos(key, &parse.options()?)?;
+        }
+        KeyCommand::Play { file, parse } if parse.strict => {
+            timeline::load(file, &parse.options()?)?;
+        }
+        KeyCommand::Type {
+            with: Some(with), ..
+        } => {
+            parse_held_keys(with)?;
+        }
+        KeyCommand::Run {
+            sequence, parse, ..
+        } if parse.strict => {
+            timeline::parse_sequence(sequence, &parse.options()?)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+impl KeyCommand {
+    /// Name of the command on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyCommand::Down { .. } => "down",
+            KeyCommand::Up { .. } => "up",
+            KeyCommand::Tap { .. } => "tap",
+            KeyCommand::Autorepeat { .. } => "autorepeat",
+            KeyCommand::Mouse { .. } => "mouse",
+            KeyCommand::MouseMove { .. } => "mouse-move",
+            KeyCommand::Scroll { .. } => "scroll",
+            KeyCommand::ReleaseAll { .. } => "release-all",
+            KeyCommand::ListKeys { .. } => "list-keys",
+            KeyCommand::Resolve { .. } => "resolve",
+            KeyCommand::Doctor { .. } => "doctor",
+            KeyCommand::Test { .. } => "test",
+            KeyCommand::Status => "status",
+            KeyCommand::CheckPermissions { .. } => "check-permissions",
+            KeyCommand::Play { .. } => "play",
+            KeyCommand::Record { .. } => "record",
+            KeyCommand::Run { .. } => "run",
+            KeyCommand::Type { .. } => "type",
+        }
+    }
+}
+
+/// Key combinations a command parsed, described for JSON output, and how
+/// many of them were sent
+#[derive(Default)]
+struct Sent {
+    combos: Vec<serde_json::Value>,
+    completed: usize,
+}
+
+pub fn execute(enigo: &mut Enigo, command: KeyCommand) -> Result<(), Error> {
+    execute_tracked(enigo, command, &mut Sent::default())
+}
+
+/// Run a command like `execute`, and describe the outcome as JSON: the
+/// action, each key combination with whether it was sent, and the error
+pub fn execute_json(
+    enigo: &mut Enigo,
+    command: KeyCommand,
+) -> (serde_json::Value, Result<(), Error>) {
+    let action = command.name();
+    let mut sent = Sent::default();
+    let result = execute_tracked(enigo, command, &mut sent);
+
+    let steps: Vec<_> = sent
+        .combos
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut step)| {
+            let status = if i < sent.completed {
+                "ok"
+            } else if i == sent.completed && result.is_err() {
+                "error"
+            } else {
+                "skipped"
+            };
+            step["status"] = status.into();
+            step
+        })
+        .collect();
+
+    let mut report = report_json(action, result.as_ref().err());
+    report["steps"] = serde_json::Value::Array(steps);
+    (report, result)
+}
+
+/// JSON result of a command with no key combinations listed, e.g. one that
+/// failed before anything was sent
+pub fn report_json(action: &str, error: Option<&Error>) -> serde_json::Value {
+    match error {
+        None => serde_json::json!({ "action": action, "ok": true, "steps": [] }),
+        Some(e) => serde_json::json!({
+            "action": action,
+            "ok": false,
+            "steps": [],
+            "error": e.to_string(),
+            "exit_code": e.exit_code(),
+        }),
+    }
+}
+
+fn execute_tracked(enigo: &mut Enigo, command: KeyCommand, sent: &mut Sent) -> Result<(), Error> {
+    if matches!(
+        command,
+        KeyCommand::Mouse { .. } | KeyCommand::MouseMove { .. } | KeyCommand::Scroll { .. }
+    ) {
+        check_mouse_allowed(command.name())?;
+    }
+
+    match command {
+        KeyCommand::Down { keys, hold } => {
+            let timing = Timing {
+                hold,
+                ..Timing::new(&keys)
+            };
+            send_keys(enigo, Action::Down, &keys, &timing, sent)?
+        }
+        KeyCommand::Up { keys } => send_keys(enigo, Action::Up, &keys, &Timing::new(&keys), sent)?,
+        KeyCommand::Tap {
+            keys,
+            hold,
+            repeat,
+            repeat_interval,
+            reset_modifiers,
+            edge,
+        } => {
+            if reset_modifiers {
+                release_modifiers(enigo)?;
+            }
+            let timing = Timing {
+                hold,
+                repeat,
+                repeat_interval,
+                edge,
+                ..Timing::new(&keys)
+            };
+            send_keys(enigo, Action::Tap, &keys, &timing, sent)?
+        }
+        KeyCommand::Autorepeat {
+            key,
+            duration,
+            parse,
+        } => {
+            let combos = parse_combos(&key, &parse.options()?)?;
+            let [(modifiers, Some(main_key))] = combos.as_slice() else {
+                return Err(Error::Parse(format!(
+                    "autorepeat needs a single key combination ending in a key to repeat, got '{}'",
+                    key
+                )));
+            };
+            autorepeat::hold(enigo, modifiers, *main_key, Duration::from_millis(duration))?;
+        }
+        KeyCommand::Mouse { action, button } => {
+            let button =
+                parse_button(&button).ok_or(format!("Unknown mouse button '{}'", button))?;
+            let direction = match action {
+                Action::Down => Direction::Press,
+                Action::Up => Direction::Release,
+                Action::Tap => Direction::Click,
+            };
+            send_button(enigo, button, direction)?;
+        }
+        KeyCommand::MouseMove { abs, x, y, .. } => {
+            let result = if abs {
+                let (x, y) = clamp_to_display(enigo, x, y);
+                enigo.move_mouse(x, y, Coordinate::Abs)
+            } else {
+                enigo.move_mouse(x, y, Coordinate::Rel)
+            };
+            result.map_err(|e| Error::Send(format!("Failed to move mouse: {}", e)))?;
+        }
+        KeyCommand::Scroll { axis, amount, unit } => {
+            let axis = match axis {
+                ScrollAxis::Vertical => Axis::Vertical,
+                ScrollAxis::Horizontal => Axis::Horizontal,
+            };
+            scroll(enigo, amount, axis, unit)
+                .map_err(|e| Error::Send(format!("Failed to scroll: {}", e)))?;
+        }
+        KeyCommand::Type {
+            text,
+            delay,
+            literal,
+            with: None,
+            verify,
+        } => {
+            type_text(enigo, &text, delay, literal)?;
+            if verify {
+                verify::verify_typed(enigo, &text)?;
+            }
+        }
+        KeyCommand::Type {
+            text,
+            delay,
+            literal,
+            with: Some(with),
+            verify,
+        } => {
+            let (modifiers, main_key) = parse_held_keys(&with)?;
+            // Keys outside the layout are injected as Unicode characters,
+            // which every platform types as-is regardless of held keys
+            if !text.is_ascii() {
+                eprintln!(
+                    "Warning: Non-ASCII characters may not be affected by the keys held with --with"
+                );
+            }
+            press_combo(enigo, &modifiers, main_key, 0)?;
+            let result = type_keys(enigo, &text, delay, literal);
+            release_combo(enigo, &modifiers, main_key, 0, ReleaseOrder::Reverse).and(result)?;
+            if verify {
+                verify::verify_typed(enigo, &text)?;
+            }
+        }
+        KeyCommand::Play { file, parse } => {
+            let steps = timeline::load(&file, &parse.options()?)?;
+            // Refuse the whole timeline rather than stop halfway through it
+            if steps
+                .iter()
+                .any(|step| matches!(step.action, timeline::StepAction::Scroll { .. }))
+            {
+                check_mouse_allowed("A timeline with scroll steps")?;
+            }
+            for (i, step) in steps.into_iter().enumerate() {
+                let delay = match step.delay_before {
+                    Some(delay) => delay,
+                    None if i > 0 => default_delay().unwrap_or(0),
+                    None => 0,
+                };
+                if delay > 0 {
+                    thread::sleep(Duration::from_millis(delay));
+                }
+                execute(enigo, step.action.into_command(&parse))?;
+            }
+        }
+        KeyCommand::Run {
+            sequence,
+            delay,
+            parse,
+        } => {
+            let steps = timeline::parse_sequence(&sequence, &parse.options()?)?;
+            for (i, action) in steps.into_iter().enumerate() {
+                if let (true, Some(delay)) = (i > 0, delay.or_else(default_delay)) {
+                    thread::sleep(Duration::from_millis(delay));
+                }
+                execute(enigo, action.into_command(&parse))?;
+            }
+        }
+        KeyCommand::ReleaseAll { keys, config } => {
+            release_all(enigo, keys.as_deref(), &release_all_options(config)?)?
+        }
+        KeyCommand::ListKeys { .. } => {
+            return Err(Error::Parse(
+                "list-keys is not available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::Doctor { .. } => {
+            return Err(Error::Parse(
+                "doctor is not available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::Test { .. } => {
+            return Err(Error::Parse(
+                "test is not available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::Resolve { .. } => {
+            return Err(Error::Parse(
+                "resolve is not available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::Status => {
+            return Err(Error::Parse(
+                "status is only available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::CheckPermissions { .. } => {
+            return Err(Error::Parse(
+                "check-permissions is not available in daemon mode".to_string(),
+            ))
+        }
+        KeyCommand::Record { .. } => {
+            return Err(Error::Parse(
+                "record is not available in daemon mode".to_string(),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// Timing of a single key combination
+struct Timing {
+    mod_delay: u64,
+    hold: Option<u64>,
+    repeat: u32,
+    repeat_interval: u64,
+    edge: Edge,
+    release_order: ReleaseOrder,
+}
+
+impl Timing {
+    fn new(keys: &KeyArgs) -> Self {
+        Self {
+            mod_delay: keys.mod_delay.or_else(default_delay).unwrap_or(0),
+            hold: None,
+            repeat: 1,
+            repeat_interval: 0,
+            edge: Edge::Press,
+            release_order: keys.release_order,
+        }
+    }
+}
+
+fn send_keys(
+    enigo: &mut Enigo,
+    action: Action,
+    keys: &KeyArgs,
+    timing: &Timing,
+    sent: &mut Sent,
+) -> Result<(), Error> {
+    // Parse every combination up front so a bad token doesn't leave a
+    // sequence half-sent
+    if keys.raw {
+        let combos = parse_raw_combos(&keys.key, keys.parse.separator()?)?;
+        log::debug!("Parsed raw keycodes '{}' as {:?}", keys.key, combos);
+        sent.combos = combos
+            .iter()
+            .map(|(modifiers, main_key)| {
+                let codes: Vec<u16> = modifiers.iter().chain(main_key).map(|key| key.0).collect();
+                serde_json::json!({ "keycodes": codes })
+            })
+            .collect();
+        send_combos(enigo, action, &combos, keys, timing, sent)
+    } else {
+        let combos = parse_combos(&keys.key, &keys.parse.options()?)?;
+        log::debug!("Parsed '{}' as {:?}", keys.key, combos);
+        sent.combos = combos
+            .iter()
+            .map(|(modifiers, main_key)| {
+                let modifiers: Vec<_> = modifiers.iter().map(|m| format!("{:?}", m)).collect();
+                serde_json::json!({
+                    "modifiers": modifiers,
+                    "key": main_key.map(|k| format!("{:?}", k)),
+                })
+            })
+            .collect();
+        send_combos(enigo, action, &combos, keys, timing, sent)
+    }
+}
+
+fn send_combos<K: SendKey>(
+    enigo: &mut Enigo,
+    action: Action,
+    combos: &[(Vec<K>, Option<K>)],
+    keys: &KeyArgs,
+    timing: &Timing,
+    sent: &mut Sent,
+) -> Result<(), Error> {
+    let toggled = match keys.numlock {
+        Some(state) => numlock::set(enigo, state)?,
+        None => false,
+    };
+
+    let mut result = Ok(());
+    for (i, (modifiers, main_key)) in combos.iter().enumerate() {
+        if i > 0 {
+            if let Some(delay) = keys.delay.or_else(default_delay) {
+                thread::sleep(Duration::from_millis(delay));
+            }
+        }
+        result = send_combo(enigo, action, modifiers, *main_key, timing);
+        if result.is_err() {
+            break;
+        }
+        sent.completed += 1;
+    }
+
+    // Restore even after a failure, and report the first error
+    if toggled && keys.restore {
+        result = result.and(numlock::toggle(enigo));
+    }
+    result
+}
+
+/// Parse a comma-separated list of `+`-joined key combinations. A comma, or
+/// the `--sep` character, escaped with a backslash is a key of its own
+pub fn parse_combos(key: &str, options: &ParseOptions) -> Result<Vec<Combo>, String> {
+    let separator = options.separator.unwrap_or(keys::DEFAULT_SEPARATOR);
+    keys::split_sequence(key, separator)
+        .iter()
+        .map(|combo| {
+            let parts: Vec<&str> = combo.split('+').collect();
+            parse_key_parts(&parts, options)
+        })
+        .collect()
+}
+
+/// Parse comma-separated combinations of `+`-joined raw keycodes. Every code
+/// but the last of a combination is held like a modifier.
+fn parse_raw_combos(key: &str, separator: char) -> Result<Vec<RawCombo>, String> {
+    keys::split_sequence(key, separator)
+        .iter()
+        .map(|combo| {
+            let mut codes = combo
+                .split('+')
+                .map(|code| {
+                    let code = code.trim();
+                    let parsed = match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+                        Some(hex) => u16::from_str_radix(hex, 16),
+                        None => code.parse(),
+                    };
+                    parsed.map(RawKey).map_err(|_| {
+                        format!(
+                            "Invalid raw keycode '{}', expected a number from 0 to 65535",
+                            code
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let main_key = codes.pop();
+            Ok((codes, main_key))
+        })
+        .collect()
+}
+
+fn send_combo<K: SendKey>(
+    enigo: &mut Enigo,
+    action: Action,
+    modifiers: &[K],
+    main_key: Option<K>,
+    timing: &Timing,
+) -> Result<(), Error> {
+    match action {
+        Action::Down => {
+            press_combo(enigo, modifiers, main_key, timing.mod_delay)?;
+            if let Some(hold) = timing.hold {
+                // Hold the whole combination, then release it
+                thread::sleep(Duration::from_millis(hold));
+                release_combo(
+                    enigo,
+                    modifiers,
+                    main_key,
+                    timing.mod_delay,
+                    timing.release_order,
+                )?;
+            }
+            Ok(())
+        }
+        Action::Up => release_combo(
+            enigo,
+            modifiers,
+            main_key,
+            timing.mod_delay,
+            timing.release_order,
+        ),
+        Action::Tap => {
+            // Press modifiers, tap main key (repeatedly), release modifiers.
+            // Keep going after a failure so nothing is left held down, and
+            // report the first error
+            let mut result = Ok(());
+            for modifier in modifiers {
+                result = result.and(modifier.send(enigo, Direction::Press));
+            }
+            modifier_pause(modifiers, timing.mod_delay);
+            // In natural order the last tap's main key stays down until the
+            // modifiers are up
+            let natural = timing.release_order == ReleaseOrder::Natural && !modifiers.is_empty();
+            let mut held = None;
+            if let Some(key) = main_key {
+                for i in 0..timing.repeat {
+                    if i > 0 {
+                        thread::sleep(Duration::from_millis(timing.repeat_interval));
+                    }
+                    let hold = match (timing.hold, timing.edge) {
+                        (Some(hold), _) => Some(Duration::from_millis(hold)),
+                        (None, Edge::Release) => Some(RELEASE_EDGE_HOLD),
+                        (None, Edge::Press) => None,
+                    };
+                    if natural && i + 1 == timing.repeat {
+                        result = result.and(key.send(enigo, Direction::Press));
+                        if let Some(hold) = hold {
+                            thread::sleep(hold);
+                        }
+                        held = Some(key);
+                        continue;
+                    }
+                    match hold {
+                        Some(hold) => {
+                            result = result.and(key.send(enigo, Direction::Press));
+                            thread::sleep(hold);
+                            result = result.and(key.send(enigo, Direction::Release));
+                        }
+                        None => {
+                            result = result.and(key.send(enigo, Direction::Click));
+                        }
+                    }
+                }
+            }
+            if natural {
+                return result.and(release_combo(
+                    enigo,
+                    modifiers,
+                    held,
+                    timing.mod_delay,
+                    ReleaseOrder::Natural,
+                ));
+            }
+            // Keep the modifiers down past the release that triggers the
+            // binding, so the target still sees the whole combination
+            if timing.edge == Edge::Release && !modifiers.is_empty() {
+                thread::sleep(RELEASE_EDGE_HOLD);
+            }
+            modifier_pause(modifiers, timing.mod_delay);
+            for modifier in modifiers.iter().rev() {
+                result = result.and(modifier.send(enigo, Direction::Release));
+            }
+            result
+        }
+    }
+}
+
+fn press_combo<K: SendKey>(
+    enigo: &mut Enigo,
+    modifiers: &[K],
+    main_key: Option<K>,
+    mod_delay: u64,
+) -> Result<(), Error> {
+    // Press modifiers first, then the main key
+    for modifier in modifiers {
+        modifier.send(enigo, Direction::Press)?;
+    }
+    modifier_pause(modifiers, mod_delay);
+    if let Some(key) = main_key {
+        key.send(enigo, Direction::Press)?;
+    }
+    Ok(())
+}
+
+fn release_combo<K: SendKey>(
+    enigo: &mut Enigo,
+    modifiers: &[K],
+    main_key: Option<K>,
+    mod_delay: u64,
+    order: ReleaseOrder,
+) -> Result<(), Error> {
+    // Reverse releases the main key first, then the modifiers last to first;
+    // natural releases the modifiers first to last, then the main key. Attempt
+    // every release even if one fails, and report the first error
+    let mut result = Ok(());
+    match order {
+        ReleaseOrder::Reverse => {
+            if let Some(key) = main_key {
+                result = result.and(key.send(enigo, Direction::Release));
+            }
+            modifier_pause(modifiers, mod_delay);
+            for modifier in modifiers.iter().rev() {
+                result = result.and(modifier.send(enigo, Direction::Release));
+            }
+        }
+        ReleaseOrder::Natural => {
+            for modifier in modifiers {
+                result = result.and(modifier.send(enigo, Direction::Release));
+            }
+            modifier_pause(modifiers, mod_delay);
+            if let Some(key) = main_key {
+                result = result.and(key.send(enigo, Direction::Release));
+            }
+        }
+    }
+    result
+}
+
+pub(crate) fn send_key(enigo: &mut Enigo, key: Key, direction: Direction) -> Result<(), Error> {
+    with_retries(|| enigo.key(key, direction)).map_err(|e| {
+        Error::Send(format!(
+            "Failed to {} key {:?}: {}",
+            verb(direction),
+            key,
+            e
+        ))
+    })?;
+
+    log::debug!("Sent {:?} {:?}", key, direction);
+    track(Held::Key(key), direction);
+    Ok(())
+}
+
+/// Inject text, retrying it whole if the OS rejects it
+fn send_text(enigo: &mut Enigo, text: &str) -> Result<(), Error> {
+    with_retries(|| enigo.text(text))
+        .map_err(|e| Error::Send(format!("Failed to type text: {}", e)))?;
+    log::debug!("Typed {:?}", text);
+    Ok(())
+}
+
+fn send_button(enigo: &mut Enigo, button: Button, direction: Direction) -> Result<(), Error> {
+    with_retries(|| enigo.button(button, direction))
+        .map_err(|e| Error::Send(format!("Failed to press mouse button: {}", e)))?;
+    log::debug!("Sent button {:?} {:?}", button, direction);
+    track(Held::Button(button), direction);
+    Ok(())
+}
+
+/// What a key event does, for error messages
+fn verb(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Press => "press",
+        Direction::Release => "release",
+        Direction::Click => "tap",
+    }
+}
+
+/// A key that can be pressed and released: a named key or a raw keycode
+trait SendKey: Copy {
+    fn send(self, enigo: &mut Enigo, direction: Direction) -> Result<(), Error>;
+}
+
+impl SendKey for Key {
+    fn send(self, enigo: &mut Enigo, direction: Direction) -> Result<(), Error> {
+        send_key(enigo, self, direction)
+    }
+}
+
+/// A platform keycode sent as is, see `KeyArgs::raw`
+#[derive(Copy, Clone, Debug)]
+struct RawKey(u16);
+
+/// Raw keycodes held like modifiers, and the main one
+type RawCombo = (Vec<RawKey>, Option<RawKey>);
+
+impl SendKey for RawKey {
+    fn send(self, enigo: &mut Enigo, direction: Direction) -> Result<(), Error> {
+        with_retries(|| enigo.raw(self.0, direction)).map_err(|e| {
+            Error::Send(format!(
+                "Failed to {} raw keycode {}: {}",
+                verb(direction),
+                self.0,
+                e
+            ))
+        })?;
+        log::debug!("Sent raw keycode {} {:?}", self.0, direction);
+        track(Held::Raw(self.0), direction);
+        Ok(())
+    }
+}
+
+/// Run an input call, retrying it shortly after if the operating system
+/// rejects the event
+fn with_retries<T>(mut send: impl FnMut() -> enigo::InputResult<T>) -> enigo::InputResult<T> {
+    let retries = SEND_RETRIES.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        match send() {
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                eprintln!(
+                    "Warning: Input event rejected ({}), retrying ({}/{})",
+                    e, attempt, retries
+                );
+                thread::sleep(SEND_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Release the given keys and then every modifier. Nothing is tracked between
+/// invocations, so this works even if the process that pressed them is gone
+fn release_all(enigo: &mut Enigo, keys: Option<&str>, options: &ParseOptions) -> Result<(), Error> {
+    let combos = match keys {
+        Some(keys) => parse_combos(keys, options)?,
+        None => Vec::new(),
+    };
+
+    let mut result = Ok(());
+    for (modifiers, main_key) in &combos {
+        result = result.and(release_combo(
+            enigo,
+            modifiers,
+            *main_key,
+            0,
+            ReleaseOrder::Reverse,
+        ));
+    }
+    result.and(release_modifiers(enigo))
+}
+
+/// Release every modifier, whether or not keystroke pressed it
+fn release_modifiers(enigo: &mut Enigo) -> Result<(), Error> {
+    let mut result = Ok(());
+    for modifier in keys::modifier_keys() {
+        result = result.and(send_key(enigo, modifier, Direction::Release));
+    }
+    result
+}
+
+/// `release-all` never guesses: a typo would leave the key stuck
+fn release_all_options(config: Option<PathBuf>) -> Result<ParseOptions, String> {
+    // Every key listed is released, so chords need no opting into, and
+    // releasing a power key can't set it off
+    ParseArgs {
+        strict: true,
+        config,
+        chord: true,
+        allow_power: true,
+        ..ParseArgs::default()
+    }
+    .options()
+}
+
+/// Give the target application time to register modifiers around the main key
+fn modifier_pause<K>(modifiers: &[K], mod_delay: u64) {
+    if !modifiers.is_empty() && mod_delay > 0 {
+        thread::sleep(Duration::from_millis(mod_delay));
+    }
+}
+
+pub fn list_keys(format: OutputFormat) {
+    let groups = keys::supported_tokens();
+
+    match format {
+        OutputFormat::Text => {
+            for (i, (category, names)) in groups.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("# {}", category);
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let groups: Vec<_> = groups
+                .into_iter()
+                .map(|(category, names)| serde_json::json!({ "category": category, "keys": names }))
+                .collect();
+            println!("{}", serde_json::Value::Array(groups));
+        }
+    }
+}
+
+/// Print the modifiers and main key of each combination in `key`
+pub fn resolve(key: &str, parse: &ParseArgs, format: OutputFormat) -> Result<(), Error> {
+    let combos = parse_combos(key, &parse.options()?)?;
+
+    match format {
+        OutputFormat::Text => {
+            for (i, (modifiers, main_key)) in combos.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("modifiers: {:?}", modifiers);
+                match main_key {
+                    Some(main_key) => println!("key: {:?}", main_key),
+                    None => println!("key: none"),
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let combos: Vec<_> = combos
+                .iter()
+                .map(|(modifiers, main_key)| {
+                    let modifiers: Vec<_> = modifiers.iter().map(|m| format!("{:?}", m)).collect();
+                    serde_json::json!({
+                        "modifiers": modifiers,
+                        "key": main_key.map(|k| format!("{:?}", k)),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(combos));
+        }
+    }
+    Ok(())
+}
+
+/// Parse the keys `type --with` holds. There is no point guessing here: a
+/// wrong key held down would change every character typed.
+fn parse_held_keys(with: &str) -> Result<Combo, String> {
+    let options = ParseOptions {
+        strict: true,
+        chord: true,
+        ..ParseOptions::default()
+    };
+    let parts: Vec<&str> = with.split('+').collect();
+    parse_key_parts(&parts, &options).map_err(|e| format!("Invalid --with keys: {}", e))
+}
+
+/// Type text as a tap of each character's key, so keys held meanwhile modify
+/// it the way they would on a physical keyboard. Newlines press Return unless
+/// `literal` is set.
+fn type_keys(
+    enigo: &mut Enigo,
+    text: &str,
+    delay: Option<u64>,
+    literal: bool,
+) -> Result<(), Error> {
+    let text = if literal {
+        text.to_string()
+    } else {
+        text.replace("\r\n", "\n")
+    };
+
+    for (i, c) in text.chars().enumerate() {
+        if let (true, Some(delay)) = (i > 0, delay) {
+            thread::sleep(Duration::from_millis(delay));
+        }
+        let key = match c {
+            '\n' if !literal => Key::Return,
+            c => Key::Unicode(c),
+        };
+        send_key(enigo, key, Direction::Click)?;
+    }
+    Ok(())
+}
+
+/// Type text in one go, or character by character when a delay is given.
+/// Newlines press Return unless `literal` is set.
+fn type_text(
+    enigo: &mut Enigo,
+    text: &str,
+    delay: Option<u64>,
+    literal: bool,
+) -> Result<(), Error> {
+    let text = if literal {
+        text.to_string()
+    } else {
+        text.replace("\r\n", "\n")
+    };
+
+    let Some(delay) = delay else {
+        if literal {
+            return send_text(enigo, &text);
+        }
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                send_key(enigo, Key::Return, Direction::Click)?;
+            }
+            if !line.is_empty() {
+                send_text(enigo, line)?;
+            }
+        }
+        return Ok(());
+    };
+
+    let mut buf = [0; 4];
+    for (i, c) in text.chars().enumerate() {
+        if i > 0 {
+            thread::sleep(Duration::from_millis(delay));
+        }
+        if c == '\n' && !literal {
+            send_key(enigo, Key::Return, Direction::Click)?;
+        } else {
+            send_text(enigo, c.encode_utf8(&mut buf))?;
+        }
+    }
+    Ok(())
+}
+
+/// Scroll by wheel steps, or by pixels where the platform supports it
+fn scroll(enigo: &mut Enigo, amount: i32, axis: Axis, unit: ScrollUnit) -> enigo::InputResult<()> {
+    if unit == ScrollUnit::Pixels {
+        #[cfg(target_os = "macos")]
+        return enigo.smooth_scroll(amount, axis);
+        #[cfg(not(target_os = "macos"))]
+        eprintln!("Warning: Scrolling by pixels is only supported on macOS, scrolling by lines");
+    }
+
+    // Issue one step at a time so each tick registers as a separate notch
+    for _ in 0..amount.unsigned_abs() {
+        enigo.scroll(amount.signum(), axis)?;
+    }
+    Ok(())
+}
+
+/// Clamp an absolute position to the primary display, warning if it was outside
+fn clamp_to_display(enigo: &Enigo, x: i32, y: i32) -> (i32, i32) {
+    let (width, height) = match enigo.main_display() {
+        // Headless X servers and some VMs report an empty display
+        Ok((width, height)) if width <= 0 || height <= 0 => {
+            eprintln!(
+                "Warning: Display size is reported as {}x{}, not clamping",
+                width, height
+            );
+            return (x, y);
+        }
+        Ok(size) => size,
+        Err(e) => {
+            eprintln!(
+                "Warning: Could not query display size ({}), not clamping",
+                e
+            );
+            return (x, y);
+        }
+    };
+
+    let clamped = (x.clamp(0, width - 1), y.clamp(0, height - 1));
+    if clamped != (x, y) {
+        eprintln!(
+            "Warning: Position ({}, {}) is outside the {}x{} display, clamping to ({}, {})",
+            x, y, width, height, clamped.0, clamped.1
+        );
+    }
+    clamped
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    match name.to_uppercase().as_str() {
+        "LEFT" => Some(Button::Left),
+        "RIGHT" => Some(Button::Right),
+        "MIDDLE" => Some(Button::Middle),
+        _ => None,
+    }
+}
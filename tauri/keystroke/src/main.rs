@@ -1,144 +1,309 @@
-use clap::{Parser, ValueEnum};
-use enigo::{Enigo, Key, Keyboard, Settings};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, FromArgMatches, Parser};
+use enigo::{Enigo, Settings};
+use keystroke::{
+    check_permissions, disable_mouse, doctor, execute, execute_json, list_keys,
+    mouse_disabled_by_env, record, report_json, resolve, self_test, server, set_send_retries,
+    validate_strict, wayland_warning, window, Error, KeyCommand, DEFAULT_SEND_RETRIES,
+    EXIT_CODES_HELP, EXIT_PARSE_ERROR, EXIT_PERMISSION_ERROR, EXIT_SEND_ERROR, LOCK_KEYS_HELP,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Log filter, e.g. "debug" or "keystroke=debug", in `env_logger` syntax
+const LOG_ENV: &str = "KEYSTROKE_LOG";
 
 #[derive(Parser)]
-#[command(name = "keystroke")]
-#[command(about = "Simulate keyboard keystrokes", long_about = None)]
+#[command(name = "keystroke", version)]
+#[command(about = "Simulate keyboard keystrokes and mouse input", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Action to perform
-    #[arg(value_enum)]
-    action: Action,
+    /// Keep running and execute newline-delimited commands read from stdin
+    #[arg(long)]
+    daemon: bool,
+
+    /// Keep running and execute commands received on this Unix socket
+    /// (or Windows named pipe, e.g. \\.\pipe\keystroke)
+    #[arg(long, value_name = "PATH", conflicts_with = "daemon")]
+    socket: Option<PathBuf>,
+
+    /// With --daemon or --socket, release any key held down for longer than
+    /// this many milliseconds
+    #[arg(long, value_name = "MS")]
+    max_hold: Option<u64>,
+
+    /// With --daemon or --socket, ignore a `tap` of the same keys repeated
+    /// within this many milliseconds, e.g. from a lever jittering on a detent
+    #[arg(long, value_name = "MS")]
+    debounce: Option<u64>,
+
+    /// Bring the window with this title (or part of it), or this window id,
+    /// to the front before sending, so input reaches it after losing focus.
+    /// Best effort: X11 and Windows only
+    #[arg(long, global = true, value_name = "TITLE_OR_ID")]
+    window: Option<String>,
+
+    /// Print the outcome as a JSON object on stdout: the action, each key
+    /// combination with whether it was sent, and the error if any
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Write the log to this file instead of stderr, at debug level unless
+    /// KEYSTROKE_LOG says otherwise
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Refuse mouse commands, for environments that only allow synthetic
+    /// keyboard input (also set by KEYSTROKE_NO_MOUSE=1)
+    #[arg(long, global = true)]
+    no_mouse: bool,
+
+    #[command(flatten)]
+    settings: SettingsArgs,
+
+    #[command(subcommand)]
+    command: Option<KeyCommand>,
+}
+
+/// Options for the input connection, given after the command or alongside
+/// `--daemon` / `--socket`
+#[derive(Args)]
+struct SettingsArgs {
+    /// Leave keys that are still held pressed when the process exits or a
+    /// daemon shuts down, instead of releasing them
+    #[arg(long, global = true)]
+    no_release_on_drop: bool,
+
+    /// Delay enigo waits after each X11 event in milliseconds (Linux only)
+    #[arg(long, global = true, value_name = "MS", default_value_t = Settings::default().linux_delay)]
+    linux_delay: u32,
 
-    /// Key combination (e.g., "W", "CTRL+S", "SHIFT+F1")
-    key: String,
+    /// Times to retry a key or mouse button event the operating system
+    /// rejects, e.g. during a focus change, before failing the command
+    #[arg(long, global = true, value_name = "N", default_value_t = DEFAULT_SEND_RETRIES)]
+    send_retries: u32,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
-enum Action {
-    /// Press key down (hold)
-    Down,
-    /// Release key
-    Up,
-    /// Press and release key
-    Tap,
+impl SettingsArgs {
+    fn to_settings(&self) -> Settings {
+        Settings {
+            release_keys_when_dropped: !self.no_release_on_drop,
+            linux_delay: self.linux_delay,
+            ..Settings::default()
+        }
+    }
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = parse_cli().unwrap_or_else(|e| exit_with_usage_error(e));
+    set_send_retries(cli.settings.send_retries);
+    if cli.no_mouse || mouse_disabled_by_env() {
+        disable_mouse();
+    }
+    if let Err(e) = init_logging(cli.log_file.as_deref()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(EXIT_PARSE_ERROR);
+    }
 
-    let mut enigo = Enigo::new(&Settings::default()).expect("Failed to initialize enigo");
+    let options = server::Options {
+        max_hold: cli.max_hold.map(Duration::from_millis),
+        window: cli.window.clone(),
+        debounce: cli.debounce.map(Duration::from_millis),
+    };
+    if cli.daemon || cli.socket.is_some() {
+        if let Some(warning) = wayland_warning() {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    if cli.daemon {
+        server::run_stdin(&mut new_enigo(&cli.settings), &options);
+        return;
+    }
 
-    // Parse the key combination
-    let parts: Vec<&str> = cli.key.split('+').collect();
-    let (modifiers, main_key) = parse_key_parts(&parts);
+    if let Some(path) = &cli.socket {
+        if let Err(e) = server::run_socket(&mut new_enigo(&cli.settings), path, &options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_SEND_ERROR);
+        }
+        return;
+    }
 
-    match cli.action {
-        Action::Down => {
-            // Press modifiers first, then the main key
-            for modifier in &modifiers {
-                let _ = enigo.key(*modifier, enigo::Direction::Press);
-            }
-            if let Some(key) = main_key {
-                let _ = enigo.key(key, enigo::Direction::Press);
-            }
+    let Some(command) = cli.command else {
+        exit_with_usage_error(Cli::command().error(
+            ErrorKind::MissingSubcommand,
+            "a command, --daemon or --socket is required",
+        ));
+    };
+
+    // Informational commands don't need an input connection
+    match command {
+        KeyCommand::ListKeys { format } => {
+            list_keys(format);
+            return;
         }
-        Action::Up => {
-            // Release main key first, then modifiers (reverse order)
-            if let Some(key) = main_key {
-                let _ = enigo.key(key, enigo::Direction::Release);
+        KeyCommand::Resolve { key, parse, format } => {
+            if let Err(e) = resolve(&key, &parse, format) {
+                eprintln!("Error: {}", e);
+                std::process::exit(e.exit_code());
             }
-            for modifier in modifiers.iter().rev() {
-                let _ = enigo.key(*modifier, enigo::Direction::Release);
+            return;
+        }
+        KeyCommand::Status => {
+            eprintln!("Error: status is only available with --daemon or --socket");
+            std::process::exit(EXIT_PARSE_ERROR);
+        }
+        KeyCommand::CheckPermissions { open, format } => {
+            if !check_permissions(open, format) {
+                std::process::exit(EXIT_PERMISSION_ERROR);
             }
+            return;
         }
-        Action::Tap => {
-            // Press modifiers, tap main key, release modifiers
-            for modifier in &modifiers {
-                let _ = enigo.key(*modifier, enigo::Direction::Press);
+        KeyCommand::Record { file, stop } => {
+            match record(&file, &stop) {
+                Ok(steps) => eprintln!("Recorded {} steps to {}", steps, file.display()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(e.exit_code());
+                }
             }
-            if let Some(key) = main_key {
-                let _ = enigo.key(key, enigo::Direction::Click);
+            return;
+        }
+        KeyCommand::Test {
+            type_text,
+            countdown,
+            format,
+        } => {
+            let mut enigo = new_enigo(&cli.settings);
+            if let Err(e) = self_test(&mut enigo, type_text, countdown, format) {
+                std::process::exit(e.exit_code());
             }
-            for modifier in modifiers.iter().rev() {
-                let _ = enigo.key(*modifier, enigo::Direction::Release);
+            return;
+        }
+        KeyCommand::Doctor { format } => {
+            // Doctor makes its own connection so it can report the failure
+            if !doctor(&cli.settings.to_settings(), format) {
+                std::process::exit(EXIT_SEND_ERROR);
             }
+            return;
         }
+        _ => {}
     }
-}
 
-fn parse_key_parts(parts: &[&str]) -> (Vec<Key>, Option<Key>) {
-    let mut modifiers = Vec::new();
-    let mut main_key = None;
-
-    for part in parts {
-        let upper = part.to_uppercase();
-        match upper.as_str() {
-            // Modifiers
-            "CTRL" | "CONTROL" => modifiers.push(Key::Control),
-            "SHIFT" => modifiers.push(Key::Shift),
-            "ALT" => modifiers.push(Key::Alt),
-            "META" | "WIN" | "SUPER" => modifiers.push(Key::Meta),
-
-            // Function keys
-            "F1" => main_key = Some(Key::F1),
-            "F2" => main_key = Some(Key::F2),
-            "F3" => main_key = Some(Key::F3),
-            "F4" => main_key = Some(Key::F4),
-            "F5" => main_key = Some(Key::F5),
-            "F6" => main_key = Some(Key::F6),
-            "F7" => main_key = Some(Key::F7),
-            "F8" => main_key = Some(Key::F8),
-            "F9" => main_key = Some(Key::F9),
-            "F10" => main_key = Some(Key::F10),
-            "F11" => main_key = Some(Key::F11),
-            "F12" => main_key = Some(Key::F12),
-
-            // Special keys
-            "SPACE" => main_key = Some(Key::Space),
-            "ENTER" | "RETURN" => main_key = Some(Key::Return),
-            "TAB" => main_key = Some(Key::Tab),
-            "ESCAPE" | "ESC" => main_key = Some(Key::Escape),
-            "BACKSPACE" => main_key = Some(Key::Backspace),
-            "DELETE" | "DEL" => main_key = Some(Key::Delete),
-            "INSERT" | "INS" => main_key = Some(Key::Insert),
-            "HOME" => main_key = Some(Key::Home),
-            "END" => main_key = Some(Key::End),
-            "PAGEUP" | "PGUP" => main_key = Some(Key::PageUp),
-            "PAGEDOWN" | "PGDN" => main_key = Some(Key::PageDown),
-
-            // Arrow keys
-            "UP" | "ARROWUP" => main_key = Some(Key::UpArrow),
-            "DOWN" | "ARROWDOWN" => main_key = Some(Key::DownArrow),
-            "LEFT" | "ARROWLEFT" => main_key = Some(Key::LeftArrow),
-            "RIGHT" | "ARROWRIGHT" => main_key = Some(Key::RightArrow),
-
-            // Numpad
-            "NUMPAD0" => main_key = Some(Key::Numpad0),
-            "NUMPAD1" => main_key = Some(Key::Numpad1),
-            "NUMPAD2" => main_key = Some(Key::Numpad2),
-            "NUMPAD3" => main_key = Some(Key::Numpad3),
-            "NUMPAD4" => main_key = Some(Key::Numpad4),
-            "NUMPAD5" => main_key = Some(Key::Numpad5),
-            "NUMPAD6" => main_key = Some(Key::Numpad6),
-            "NUMPAD7" => main_key = Some(Key::Numpad7),
-            "NUMPAD8" => main_key = Some(Key::Numpad8),
-            "NUMPAD9" => main_key = Some(Key::Numpad9),
-
-            // Single character (letter or number)
-            s if s.len() == 1 => {
-                let c = s.chars().next().unwrap();
-                main_key = Some(Key::Unicode(c.to_ascii_lowercase()));
+    // `type -` types whatever is piped in
+    let command = match command {
+        KeyCommand::Type {
+            text,
+            delay,
+            literal,
+            with,
+            verify,
+        } if text == "-" => match std::io::read_to_string(std::io::stdin()) {
+            Ok(text) => KeyCommand::Type {
+                text,
+                delay,
+                literal,
+                with,
+                verify,
+            },
+            Err(e) => {
+                eprintln!("Error: Failed to read text from stdin: {}", e);
+                std::process::exit(EXIT_PARSE_ERROR);
             }
+        },
+        command => command,
+    };
 
-            // Unknown key - try as unicode
-            s => {
-                eprintln!("Warning: Unknown key '{}', treating as unicode", s);
-                if let Some(c) = s.chars().next() {
-                    main_key = Some(Key::Unicode(c.to_ascii_lowercase()));
-                }
-            }
+    let action = command.name();
+    log::debug!(
+        "Command: {}",
+        std::env::args().skip(1).collect::<Vec<_>>().join(" ")
+    );
+    let fail = |e: Error| -> ! {
+        log::debug!("{} failed: {}", action, e);
+        if cli.json {
+            println!("{}", report_json(action, Some(&e)));
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        std::process::exit(e.exit_code());
+    };
+
+    // In strict mode, reject invalid key names before connecting so a
+    // mapping can be checked where no input system is available (e.g. in CI)
+    if let Err(e) = validate_strict(&command) {
+        fail(e);
+    }
+
+    let mut enigo = connect(&cli.settings).unwrap_or_else(|e| fail(e));
+    // Sending to whatever has focus instead of a window that doesn't exist
+    // could trigger the wrong binding, so that fails the command
+    if let Some(target) = &cli.window {
+        if let Err(e) = window::focus_existing(target) {
+            fail(e);
+        }
+    }
+    if cli.json {
+        let (report, result) = execute_json(&mut enigo, command);
+        println!("{}", report);
+        if let Err(e) = result {
+            std::process::exit(e.exit_code());
         }
+    } else if let Err(e) = execute(&mut enigo, command) {
+        fail(e);
     }
+    log::debug!("{} done", action);
+}
+
+/// Parse the command line, with the lock key notes and exit statuses at the
+/// end of the top-level help
+fn parse_cli() -> Result<Cli, clap::Error> {
+    let matches = Cli::command()
+        .after_help(format!("{}\n\n{}", LOCK_KEYS_HELP, EXIT_CODES_HELP))
+        .try_get_matches()?;
+    Cli::from_arg_matches(&matches)
+}
+
+/// Set up the log, which stays off unless KEYSTROKE_LOG or a log file is given
+fn init_logging(log_file: Option<&Path>) -> Result<(), String> {
+    let default_filter = if log_file.is_some() { "debug" } else { "off" };
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::new().filter_or(LOG_ENV, default_filter));
+    if let Some(path) = log_file {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open log file '{}': {}", path.display(), e))?;
+        builder.target(env_logger::Target::Pipe(Box::new(file)));
+    }
+    builder.init();
+    Ok(())
+}
+
+/// Exit on a clap error, reporting usage errors as parse failures rather than
+/// clap's default status 2
+fn exit_with_usage_error(error: clap::Error) -> ! {
+    if !error.use_stderr() {
+        // --help and --version
+        error.exit();
+    }
+    let _ = error.print();
+    std::process::exit(EXIT_PARSE_ERROR);
+}
+
+fn connect(settings: &SettingsArgs) -> Result<Enigo, Error> {
+    Enigo::new(&settings.to_settings()).map_err(|e| {
+        let mut message = format!("Failed to connect to the input system: {}", e);
+        if let Some(warning) = wayland_warning() {
+            message.push_str(&format!(" ({})", warning));
+        }
+        Error::Send(message)
+    })
+}
 
-    (modifiers, main_key)
+fn new_enigo(settings: &SettingsArgs) -> Enigo {
+    connect(settings).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    })
 }
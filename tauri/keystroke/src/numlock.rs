@@ -0,0 +1,111 @@
+//! `--numlock`: put NumLock in a known state before sending keys.
+//!
+//! With NumLock off, most layouts turn the numpad into navigation keys, so
+//! whether `NUMPAD4` types a 4 or moves left depends on the machine. The state
+//! is read from the OS and NumLock is tapped only if it differs. macOS has no
+//! NumLock, so asking for a state there is an error.
+
+use enigo::Enigo;
+#[cfg(not(target_os = "macos"))]
+use enigo::{Direction, Key};
+
+use crate::{Error, LockState};
+
+/// Make NumLock match `state`. Returns whether it had to be toggled, so the
+/// caller can toggle it back afterwards.
+pub fn set(enigo: &mut Enigo, state: LockState) -> Result<bool, Error> {
+    let on = platform::numlock_on()
+        .map_err(|e| Error::Send(format!("Could not read the NumLock state: {}", e)))?;
+    if on == (state == LockState::On) {
+        return Ok(false);
+    }
+    log::debug!("Turning NumLock {}", if on { "off" } else { "on" });
+    toggle(enigo)?;
+    Ok(true)
+}
+
+/// Tap NumLock once, flipping its state
+#[cfg(not(target_os = "macos"))]
+pub fn toggle(enigo: &mut Enigo) -> Result<(), Error> {
+    crate::send_key(enigo, Key::Numlock, Direction::Click)
+}
+
+#[cfg(target_os = "macos")]
+pub fn toggle(_enigo: &mut Enigo) -> Result<(), Error> {
+    Err(Error::Send("This platform has no NumLock".to_string()))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::ConnectionExt;
+
+    /// XK_Num_Lock
+    const NUM_LOCK_KEYSYM: u32 = 0xff7f;
+
+    /// Whether the modifier NumLock is mapped to (usually Mod2) is locked
+    pub fn numlock_on() -> Result<bool, String> {
+        let error = |e: &dyn std::fmt::Display| format!("X11 error: {}", e);
+        let (conn, screen) = x11rb::connect(None).map_err(|e| {
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                "the NumLock state can't be read on Wayland".to_string()
+            } else {
+                format!("Failed to connect to X11: {}", e)
+            }
+        })?;
+        let setup = conn.setup();
+        let root = setup.roots[screen].root;
+
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn
+            .get_keyboard_mapping(min, max - min + 1)
+            .map_err(|e| error(&e))?
+            .reply()
+            .map_err(|e| error(&e))?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        let keycodes: Vec<u8> = mapping
+            .keysyms
+            .chunks(per_keycode)
+            .enumerate()
+            .filter(|(_, keysyms)| keysyms.contains(&NUM_LOCK_KEYSYM))
+            .map(|(i, _)| min + i as u8)
+            .collect();
+
+        let modifiers = conn
+            .get_modifier_mapping()
+            .map_err(|e| error(&e))?
+            .reply()
+            .map_err(|e| error(&e))?;
+        let per_modifier = usize::from(modifiers.keycodes_per_modifier()).max(1);
+        let index = modifiers
+            .keycodes
+            .chunks(per_modifier)
+            .position(|codes| codes.iter().any(|code| keycodes.contains(code)))
+            .ok_or("NumLock isn't mapped to a modifier")?;
+
+        let pointer = conn
+            .query_pointer(root)
+            .map_err(|e| error(&e))?
+            .reply()
+            .map_err(|e| error(&e))?;
+        Ok(u16::from(pointer.mask) & (1 << index) != 0)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetKeyState, VK_NUMLOCK};
+
+    /// Whether the toggle bit of the NumLock key is set
+    pub fn numlock_on() -> Result<bool, String> {
+        // SAFETY: GetKeyState only reads the thread's keyboard state
+        Ok(unsafe { GetKeyState(i32::from(VK_NUMLOCK)) } & 1 != 0)
+    }
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+mod platform {
+    pub fn numlock_on() -> Result<bool, String> {
+        Err("this platform has no NumLock".to_string())
+    }
+}
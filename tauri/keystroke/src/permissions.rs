@@ -0,0 +1,183 @@
+//! `keystroke check-permissions`: whether the OS lets this process send input.
+//!
+//! Only macOS gates synthetic input behind a permission. Until the app running
+//! keystroke is allowed under Accessibility, every event is refused.
+
+use serde_json::json;
+
+use crate::OutputFormat;
+
+/// A permission the OS grants per application
+struct Permission {
+    name: &'static str,
+    granted: bool,
+    /// Whether input can't be sent without it
+    required: bool,
+    /// Where the user grants it
+    settings_url: &'static str,
+}
+
+/// Check the permissions, print a report and, with `open`, open the settings
+/// pane of each missing required one. Returns whether every required one is granted.
+pub fn check_permissions(open: bool, format: OutputFormat) -> bool {
+    let permissions = platform::permissions();
+    let ok = permissions.iter().all(|p| p.granted || !p.required);
+
+    let mut open_errors = Vec::new();
+    if open {
+        for permission in permissions.iter().filter(|p| p.required && !p.granted) {
+            if let Err(e) = open_settings(permission.settings_url) {
+                open_errors.push(format!("{}: {}", permission.name, e));
+            }
+        }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            if permissions.is_empty() {
+                println!(
+                    "No permission is needed to send input on {}",
+                    std::env::consts::OS
+                );
+            }
+            for permission in &permissions {
+                let optional = if permission.required {
+                    ""
+                } else {
+                    " (optional)"
+                };
+                if permission.granted {
+                    println!("{}: granted", permission.name);
+                } else {
+                    println!("{}: not granted{}", permission.name, optional);
+                }
+            }
+            if let Some(note) = platform::NOTE {
+                println!();
+                println!("Note: {}", note);
+            }
+            if !ok {
+                println!();
+                println!("{}", platform::INSTRUCTIONS);
+                if !open {
+                    println!("Run with --open to go to the settings.");
+                }
+            }
+            for error in &open_errors {
+                eprintln!("Warning: Could not open the settings for {}", error);
+            }
+        }
+        OutputFormat::Json => {
+            let permissions: Vec<_> = permissions
+                .iter()
+                .map(|p| {
+                    json!({
+                        "name": p.name,
+                        "granted": p.granted,
+                        "required": p.required,
+                        "settings_url": p.settings_url,
+                    })
+                })
+                .collect();
+            let report = json!({
+                "os": std::env::consts::OS,
+                "ok": ok,
+                "permissions": permissions,
+                "instructions": (!ok).then_some(platform::INSTRUCTIONS),
+            });
+            println!("{}", report);
+        }
+    }
+
+    ok
+}
+
+#[cfg(target_os = "macos")]
+fn open_settings(url: &str) -> Result<(), String> {
+    let status = std::process::Command::new("open")
+        .arg(url)
+        .status()
+        .map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("open exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn open_settings(_url: &str) -> Result<(), String> {
+    Err("there is nothing to open on this platform".to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Permission;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightListenEventAccess() -> bool;
+    }
+
+    pub const INSTRUCTIONS: &str = "Keystrokes are refused until the app running keystroke \
+        (Trenino, or the terminal during development) is enabled in System Settings > \
+        Privacy & Security > Accessibility. Enable it there, then restart the app.";
+
+    pub const NOTE: Option<&str> = None;
+
+    pub fn permissions() -> Vec<Permission> {
+        // SAFETY: both only query the permission database, without prompting
+        let (accessibility, input_monitoring) =
+            unsafe { (AXIsProcessTrusted(), CGPreflightListenEventAccess()) };
+        vec![
+            Permission {
+                name: "Accessibility",
+                granted: accessibility,
+                required: true,
+                settings_url:
+                    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility",
+            },
+            Permission {
+                // Only needed to read input, not to send it
+                name: "Input Monitoring",
+                granted: input_monitoring,
+                required: false,
+                settings_url:
+                    "x-apple.systempreferences:com.apple.preference.security?Privacy_ListenEvent",
+            },
+        ]
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Permission;
+
+    pub const INSTRUCTIONS: &str = "";
+
+    pub const NOTE: Option<&str> = Some(
+        "Input can't reach an application running as administrator unless keystroke \
+         runs as administrator too.",
+    );
+
+    pub fn permissions() -> Vec<Permission> {
+        Vec::new()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::Permission;
+
+    pub const INSTRUCTIONS: &str = "";
+
+    pub const NOTE: Option<&str> = None;
+
+    pub fn permissions() -> Vec<Permission> {
+        Vec::new()
+    }
+}
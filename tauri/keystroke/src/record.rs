@@ -0,0 +1,556 @@
+//! `keystroke record`: capture keys pressed by hand as a timeline for `play`.
+//!
+//! Every key press and release anywhere on the desktop becomes a `down` or
+//! `up` step, with the time since the previous step as `delay_before`, until
+//! the stop key is pressed. Holding a key down doesn't add steps for its
+//! autorepeat, and keys already held when recording started are left out.
+//!
+//! enigo can only send input, so keys are read with the platform's own
+//! facility: the RECORD extension on X11 and a low-level keyboard hook on
+//! Windows. Neither macOS nor Wayland offer one keystroke can use.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::keys::{self, KEY_GROUPS};
+use crate::Error;
+
+/// A timeline step, in the format `timeline::load` reads
+#[derive(Serialize)]
+struct RecordedStep {
+    action: &'static str,
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delay_before: Option<u64>,
+}
+
+/// Turns key events into timeline steps
+struct Recorder {
+    stop: String,
+    held: Vec<String>,
+    steps: Vec<RecordedStep>,
+    last_step: Option<Instant>,
+    /// Codes of keys without a name, warned about once each
+    skipped: HashSet<u32>,
+}
+
+impl Recorder {
+    fn new(stop: String) -> Self {
+        Recorder {
+            stop,
+            held: Vec::new(),
+            steps: Vec::new(),
+            last_step: None,
+            skipped: HashSet::new(),
+        }
+    }
+
+    /// Record a key event. Returns false once the stop key is pressed.
+    fn event(&mut self, code: u32, name: Option<String>, pressed: bool, at: Instant) -> bool {
+        let Some(name) = name else {
+            if self.skipped.insert(code) {
+                eprintln!(
+                    "Warning: Skipping the key with code {}, which has no name",
+                    code
+                );
+            }
+            return true;
+        };
+        if name == self.stop {
+            return !pressed;
+        }
+
+        let held = self.held.iter().position(|key| *key == name);
+        let action = match (pressed, held) {
+            (true, None) => {
+                self.held.push(name.clone());
+                "down"
+            }
+            (false, Some(i)) => {
+                self.held.remove(i);
+                "up"
+            }
+            // Autorepeat, or the release of a key pressed before recording
+            _ => return true,
+        };
+
+        log::debug!("Recorded {} {}", action, name);
+        self.steps.push(RecordedStep {
+            action,
+            key: name,
+            delay_before: self
+                .last_step
+                .map(|last| at.duration_since(last).as_millis() as u64),
+        });
+        self.last_step = Some(at);
+        true
+    }
+}
+
+/// The name recorded steps use for a key, i.e. the first of its names, or the
+/// lowercase letter or digit
+fn canonical_name(name: &str) -> Option<String> {
+    let upper = name.trim().to_uppercase();
+    let named = KEY_GROUPS
+        .iter()
+        .flat_map(|(_, keys)| keys.iter())
+        .find(|key| key.names.contains(&upper.as_str()));
+    match named {
+        Some(key) => Some(key.names[0].to_string()),
+        None if keys::function_key(&upper).is_ok() => Some(upper),
+        None if upper.len() == 1 && upper.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            Some(upper.to_lowercase())
+        }
+        None => None,
+    }
+}
+
+/// Record keys until `stop` is pressed and write them to `path` as a timeline.
+/// Returns how many steps were recorded.
+pub fn record(path: &Path, stop: &str) -> Result<usize, Error> {
+    let stop =
+        canonical_name(stop).ok_or_else(|| Error::Parse(format!("Unknown stop key '{}'", stop)))?;
+    let mut recorder = Recorder::new(stop.clone());
+
+    eprintln!("Recording, press {} to stop", stop);
+    platform::listen(&mut |code, name, pressed| {
+        recorder.event(code, name, pressed, Instant::now())
+    })
+    .map_err(Error::Send)?;
+    for key in &recorder.held {
+        eprintln!("Warning: {} was still held when recording stopped", key);
+    }
+
+    let json = serde_json::to_string_pretty(&recorder.steps)
+        .map_err(|e| Error::Send(format!("Failed to write the timeline: {}", e)))?;
+    std::fs::write(path, json + "\n")
+        .map_err(|e| Error::Send(format!("Failed to write '{}': {}", path.display(), e)))?;
+    Ok(recorder.steps.len())
+}
+
+/// Name of an X11 keysym as keystroke writes it
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keysym_name(keysym: u32) -> Option<String> {
+    let name = match keysym {
+        0x30..=0x39 | 0x41..=0x5a | 0x61..=0x7a => {
+            // Lowercase, since auto-shift would replay "S" as Shift+S
+            return Some((keysym as u8 as char).to_ascii_lowercase().to_string());
+        }
+        0xffbe..=0xffd5 => return Some(format!("F{}", keysym - 0xffbe + 1)),
+        0xffb0..=0xffb9 => return Some(format!("NUMPAD{}", keysym - 0xffb0)),
+        0x20 => "SPACE",
+        0x2c => "COMMA",
+        0x2d => "MINUS",
+        0x2e => "PERIOD",
+        0x2f => "SLASH",
+        0x3b => "SEMICOLON",
+        0x3d => "EQUALS",
+        0x5b => "LEFTBRACKET",
+        0x5c => "BACKSLASH",
+        0x5d => "RIGHTBRACKET",
+        0x60 => "GRAVE",
+        0xfe03 => "RALT", // ISO_Level3_Shift, i.e. AltGr
+        0xff08 => "BACKSPACE",
+        0xff09 => "TAB",
+        0xff0d => "ENTER",
+        0xff13 => "PAUSE",
+        0xff14 => "SCROLLLOCK",
+        0xff1b => "ESCAPE",
+        0xff50 => "HOME",
+        0xff51 => "LEFT",
+        0xff52 => "UP",
+        0xff53 => "RIGHT",
+        0xff54 => "DOWN",
+        0xff55 => "PAGEUP",
+        0xff56 => "PAGEDOWN",
+        0xff57 => "END",
+        0xff61 => "PRINTSCREEN",
+        0xff63 => "INSERT",
+        0xff67 => "MENU",
+        0xff7f => "NUMLOCK",
+        0xff8d => "NUMPADENTER",
+        0xffaa => "NUMPADMULTIPLY",
+        0xffab => "NUMPADADD",
+        0xffad => "NUMPADSUBTRACT",
+        0xffae => "NUMPADDECIMAL",
+        0xffaf => "NUMPADDIVIDE",
+        0xffe1 => "SHIFT",
+        0xffe2 => "RSHIFT",
+        0xffe3 => "CTRL",
+        0xffe4 => "RCTRL",
+        0xffe5 => "CAPSLOCK",
+        0xffe9 => "ALT",
+        0xffea => "RALT",
+        0xffeb => "META",
+        0xffec => "RMETA",
+        0xffff => "DELETE",
+        0x1008ff11 => "VOLUMEDOWN",
+        0x1008ff12 => "MUTE",
+        0x1008ff13 => "VOLUMEUP",
+        0x1008ff14 => "PLAYPAUSE",
+        0x1008ff15 => "STOP",
+        0x1008ff16 => "PREVTRACK",
+        0x1008ff17 => "NEXTTRACK",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::record::{self, ConnectionExt as _};
+    use x11rb::protocol::xproto::{self, ConnectionExt as _, KeyPressEvent};
+    use x11rb::x11_utils::TryParse;
+
+    /// Sent once the server starts recording, before any data
+    const START_OF_DATA: u8 = 4;
+    /// Data the server generated, such as device events
+    const FROM_SERVER: u8 = 0;
+
+    /// Call `on_key` with each key pressed or released until it returns false
+    pub fn listen(on_key: &mut dyn FnMut(u32, Option<String>, bool) -> bool) -> Result<(), String> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Err("recording keys is not supported on Wayland".to_string());
+        }
+        let error = |e: &dyn std::fmt::Display| format!("X11 error: {}", e);
+        let connect = || {
+            x11rb::connect(None)
+                .map(|(conn, _)| conn)
+                .map_err(|e| format!("Failed to connect to X11: {}", e))
+        };
+        // Recording takes over a connection, so requests need another one
+        let control = connect()?;
+        let data = connect()?;
+
+        if control
+            .extension_information(record::X11_EXTENSION_NAME)
+            .map_err(|e| error(&e))?
+            .is_none()
+        {
+            return Err("the X server doesn't support the RECORD extension".to_string());
+        }
+
+        let setup = control.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let mapping = control
+            .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)
+            .map_err(|e| error(&e))?
+            .reply()
+            .map_err(|e| error(&e))?;
+        let per_keycode = mapping.keysyms_per_keycode as usize;
+        // The first keysym with a name, so Shift+1 records SHIFT and 1
+        let name = |keycode: u8| {
+            let start = keycode.checked_sub(min_keycode)? as usize * per_keycode;
+            mapping
+                .keysyms
+                .get(start..start + per_keycode)?
+                .iter()
+                .find_map(|&keysym| super::keysym_name(keysym))
+        };
+
+        let context = control.generate_id().map_err(|e| error(&e))?;
+        let none = record::Range8 { first: 0, last: 0 };
+        let no_extension = record::ExtRange {
+            major: none,
+            minor: record::Range16 { first: 0, last: 0 },
+        };
+        let range = record::Range {
+            core_requests: none,
+            core_replies: none,
+            ext_requests: no_extension,
+            ext_replies: no_extension,
+            delivered_events: none,
+            device_events: record::Range8 {
+                first: xproto::KEY_PRESS_EVENT,
+                last: xproto::KEY_RELEASE_EVENT,
+            },
+            errors: none,
+            client_started: false,
+            client_died: false,
+        };
+        control
+            .record_create_context(context, 0, &[record::CS::ALL_CLIENTS.into()], &[range])
+            .map_err(|e| error(&e))?
+            .check()
+            .map_err(|e| error(&e))?;
+
+        'recording: for reply in data.record_enable_context(context).map_err(|e| error(&e))? {
+            let reply = reply.map_err(|e| error(&e))?;
+            match reply.category {
+                START_OF_DATA => log::debug!("Recording started"),
+                FROM_SERVER => {
+                    // Device events are always 32 bytes long
+                    for raw in reply.data.chunks_exact(32) {
+                        let pressed = match raw[0] & 0x7f {
+                            xproto::KEY_PRESS_EVENT => true,
+                            xproto::KEY_RELEASE_EVENT => false,
+                            _ => continue,
+                        };
+                        let Ok((event, _)) = KeyPressEvent::try_parse(raw) else {
+                            continue;
+                        };
+                        if !on_key(event.detail.into(), name(event.detail), pressed) {
+                            break 'recording;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let _ = control.record_disable_context(context);
+        let _ = control.record_free_context(context);
+        let _ = control.flush();
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use windows_sys::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, PeekMessageW, PostThreadMessageW, SetWindowsHookExW,
+        UnhookWindowsHookEx, HC_ACTION, KBDLLHOOKSTRUCT, LLKHF_EXTENDED, LLKHF_INJECTED, MSG,
+        PM_NOREMOVE, WH_KEYBOARD_LL, WM_KEYDOWN, WM_QUIT, WM_SYSKEYDOWN, WM_USER,
+    };
+
+    /// Virtual-key code, extended flag and whether the key went down, from
+    /// the hook to the thread calling `on_key`
+    static EVENTS: Mutex<Option<mpsc::Sender<(u32, bool, bool)>>> = Mutex::new(None);
+
+    /// Call `on_key` with each key pressed or released until it returns false
+    pub fn listen(on_key: &mut dyn FnMut(u32, Option<String>, bool) -> bool) -> Result<(), String> {
+        let (tx, events) = mpsc::channel();
+        *EVENTS.lock().unwrap() = Some(tx);
+
+        // The hook is called on the thread that installed it while that
+        // thread waits for messages, so it gets one of its own
+        let (ready_tx, ready) = mpsc::channel();
+        let hook_thread = thread::spawn(move || {
+            // SAFETY: the hook procedure matches HOOKPROC, and `msg` is only
+            // written by the message functions
+            unsafe {
+                let hook = SetWindowsHookExW(
+                    WH_KEYBOARD_LL,
+                    Some(keyboard_hook),
+                    GetModuleHandleW(std::ptr::null()),
+                    0,
+                );
+                if hook.is_null() {
+                    let _ = ready_tx.send(Err(std::io::Error::last_os_error().to_string()));
+                    return;
+                }
+                // Create the message queue before anyone posts to it
+                let mut msg: MSG = std::mem::zeroed();
+                PeekMessageW(
+                    &mut msg,
+                    std::ptr::null_mut(),
+                    WM_USER,
+                    WM_USER,
+                    PM_NOREMOVE,
+                );
+                let _ = ready_tx.send(Ok(GetCurrentThreadId()));
+                while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {}
+                UnhookWindowsHookEx(hook);
+            }
+        });
+
+        let thread_id = match ready.recv() {
+            Ok(Ok(thread_id)) => thread_id,
+            Ok(Err(e)) => return Err(format!("Failed to install the keyboard hook: {}", e)),
+            Err(_) => return Err("Failed to install the keyboard hook".to_string()),
+        };
+        for (vk, extended, pressed) in events {
+            if !on_key(vk, vk_name(vk, extended), pressed) {
+                break;
+            }
+        }
+
+        EVENTS.lock().unwrap().take();
+        // SAFETY: posting to a thread id has no memory safety requirements
+        unsafe {
+            PostThreadMessageW(thread_id, WM_QUIT, 0, 0);
+        }
+        let _ = hook_thread.join();
+        Ok(())
+    }
+
+    unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code == HC_ACTION as i32 {
+            // SAFETY: for HC_ACTION, lparam points to the event's KBDLLHOOKSTRUCT
+            let event = unsafe { &*(lparam as *const KBDLLHOOKSTRUCT) };
+            // Input sent by programs, including keystroke itself, isn't recorded
+            if event.flags & LLKHF_INJECTED == 0 {
+                let pressed = matches!(wparam as u32, WM_KEYDOWN | WM_SYSKEYDOWN);
+                let extended = event.flags & LLKHF_EXTENDED != 0;
+                if let Some(events) = EVENTS.lock().ok().as_ref().and_then(|e| e.as_ref()) {
+                    let _ = events.send((event.vkCode, extended, pressed));
+                }
+            }
+        }
+        // SAFETY: passes the event on unchanged; the hook handle is ignored
+        unsafe { CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam) }
+    }
+
+    /// Name of a virtual-key code as keystroke writes it. Punctuation is
+    /// named after the US layout.
+    fn vk_name(vk: u32, extended: bool) -> Option<String> {
+        let name = match vk {
+            // Lowercase, since auto-shift would replay "S" as Shift+S
+            0x30..=0x39 | 0x41..=0x5a => {
+                return Some((vk as u8 as char).to_ascii_lowercase().to_string())
+            }
+            0x60..=0x69 => return Some(format!("NUMPAD{}", vk - 0x60)),
+            0x70..=0x87 => return Some(format!("F{}", vk - 0x70 + 1)),
+            0x0d if extended => "NUMPADENTER",
+            0x08 => "BACKSPACE",
+            0x09 => "TAB",
+            0x0d => "ENTER",
+            0x13 => "PAUSE",
+            0x14 => "CAPSLOCK",
+            0x1b => "ESCAPE",
+            0x20 => "SPACE",
+            0x21 => "PAGEUP",
+            0x22 => "PAGEDOWN",
+            0x23 => "END",
+            0x24 => "HOME",
+            0x25 => "LEFT",
+            0x26 => "UP",
+            0x27 => "RIGHT",
+            0x28 => "DOWN",
+            0x2c => "PRINTSCREEN",
+            0x2d => "INSERT",
+            0x2e => "DELETE",
+            0x5b => "META",
+            0x5c => "RMETA",
+            0x5d => "MENU",
+            0x6a => "NUMPADMULTIPLY",
+            0x6b => "NUMPADADD",
+            0x6d => "NUMPADSUBTRACT",
+            0x6e => "NUMPADDECIMAL",
+            0x6f => "NUMPADDIVIDE",
+            0x90 => "NUMLOCK",
+            0x91 => "SCROLLLOCK",
+            0xa0 => "SHIFT",
+            0xa1 => "RSHIFT",
+            0xa2 => "CTRL",
+            0xa3 => "RCTRL",
+            0xa4 => "ALT",
+            0xa5 => "RALT",
+            0xad => "MUTE",
+            0xae => "VOLUMEDOWN",
+            0xaf => "VOLUMEUP",
+            0xb0 => "NEXTTRACK",
+            0xb1 => "PREVTRACK",
+            0xb2 => "STOP",
+            0xb3 => "PLAYPAUSE",
+            0xba => "SEMICOLON",
+            0xbb => "EQUALS",
+            0xbc => "COMMA",
+            0xbd => "MINUS",
+            0xbe => "PERIOD",
+            0xbf => "SLASH",
+            0xc0 => "GRAVE",
+            0xdb => "LEFTBRACKET",
+            0xdc => "BACKSLASH",
+            0xdd => "RIGHTBRACKET",
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+mod platform {
+    pub fn listen(
+        _on_key: &mut dyn FnMut(u32, Option<String>, bool) -> bool,
+    ) -> Result<(), String> {
+        Err("recording keys is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn records_presses_and_releases() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let key = |name: &str| Some(name.to_string());
+        let mut recorder = Recorder::new("ESCAPE".to_string());
+
+        // Released before anything was recorded, e.g. Enter from the shell
+        assert!(recorder.event(36, key("ENTER"), false, at(0)));
+        assert!(recorder.event(37, key("CTRL"), true, at(100)));
+        assert!(recorder.event(39, key("s"), true, at(150)));
+        // Autorepeat
+        assert!(recorder.event(39, key("s"), true, at(180)));
+        assert!(recorder.event(39, key("s"), false, at(200)));
+        assert!(recorder.event(37, key("CTRL"), false, at(260)));
+        assert!(recorder.event(135, None, true, at(300)));
+        assert!(!recorder.event(9, key("ESCAPE"), true, at(400)));
+
+        let steps: Vec<_> = recorder
+            .steps
+            .iter()
+            .map(|step| (step.action, step.key.as_str(), step.delay_before))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("down", "CTRL", None),
+                ("down", "s", Some(50)),
+                ("up", "s", Some(50)),
+                ("up", "CTRL", Some(60)),
+            ]
+        );
+        assert!(recorder.held.is_empty());
+    }
+
+    #[test]
+    fn stop_key_names() {
+        assert_eq!(canonical_name("esc").as_deref(), Some("ESCAPE"));
+        assert_eq!(canonical_name("f12").as_deref(), Some("F12"));
+        assert_eq!(canonical_name("Q").as_deref(), Some("q"));
+        assert_eq!(canonical_name("SHFT"), None);
+    }
+
+    /// Every name the recorder writes must play back as that key alone, with
+    /// the options `play` uses
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn recorded_names_parse() {
+        let strict = crate::ParseArgs {
+            strict: true,
+            ..crate::ParseArgs::default()
+        }
+        .options()
+        .unwrap();
+        for keysym in (0..=0xffff).chain(0x1008ff00..=0x1008ffff) {
+            if let Some(name) = keysym_name(keysym) {
+                let combos = crate::parse_combos(&name, &strict)
+                    .unwrap_or_else(|e| panic!("{} doesn't parse: {}", name, e));
+                let [(modifiers, main_key)] = combos.as_slice() else {
+                    panic!("{} is more than one combination", name);
+                };
+                // A modifier is recorded as itself, anything else adds none
+                assert!(
+                    main_key.is_none() || modifiers.is_empty(),
+                    "{} plays back with {:?} held",
+                    name,
+                    modifiers
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! `keystroke test`: send a known sequence and report how each step went.
+//!
+//! Where `doctor` inspects the environment, this sends input through the same
+//! code as regular commands, so a pass means commands like these will work.
+//! The default steps only press modifiers, which have no effect on their own;
+//! typing text is opt-in since it goes to whatever has focus.
+
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use enigo::Enigo;
+use serde_json::json;
+
+use crate::{execute, Edge, Error, KeyArgs, KeyCommand, OutputFormat, ParseArgs};
+
+/// Text typed and read back with `--type`
+const TEST_TEXT: &str = "Trenino keystroke test";
+
+/// One step of the test and how it went
+struct Step {
+    name: &'static str,
+    result: Result<(), Error>,
+}
+
+/// The steps sent by default, each harmless in any focused application
+fn modifier_steps() -> Vec<(&'static str, KeyCommand)> {
+    let keys = |key: &str| KeyArgs::new(key.to_string(), ParseArgs::default());
+    let tap = |key: &str, hold| KeyCommand::Tap {
+        keys: keys(key),
+        hold,
+        repeat: 1,
+        repeat_interval: 0,
+        reset_modifiers: false,
+        edge: Edge::Press,
+    };
+    vec![
+        ("Tap Shift", tap("SHIFT", None)),
+        ("Hold Ctrl", tap("CTRL", Some(50))),
+        (
+            "Press and release Shift",
+            KeyCommand::Down {
+                keys: keys("SHIFT"),
+                hold: Some(20),
+            },
+        ),
+    ]
+}
+
+/// Run the test and print a report. With `type_text`, waits `countdown` for
+/// the user to focus a text field, then types into it and reads it back.
+/// Returns the first failure, if any.
+pub fn self_test(
+    enigo: &mut Enigo,
+    type_text: bool,
+    countdown: u64,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let mut steps: Vec<Step> = modifier_steps()
+        .into_iter()
+        .map(|(name, command)| Step {
+            name,
+            result: execute(enigo, command),
+        })
+        .collect();
+
+    if type_text {
+        if format == OutputFormat::Text {
+            wait_for_focus(countdown);
+        } else {
+            thread::sleep(Duration::from_secs(countdown));
+        }
+        steps.push(Step {
+            name: "Type and read back text",
+            result: execute(
+                enigo,
+                KeyCommand::Type {
+                    text: TEST_TEXT.to_string(),
+                    delay: None,
+                    literal: false,
+                    with: None,
+                    verify: true,
+                },
+            ),
+        });
+    }
+
+    match format {
+        OutputFormat::Text => {
+            for step in &steps {
+                match &step.result {
+                    Ok(()) => println!("{}: ok", step.name),
+                    Err(e) => println!("{}: failed ({})", step.name, e),
+                }
+            }
+            if steps.iter().all(|step| step.result.is_ok()) {
+                println!("Input works");
+            }
+        }
+        OutputFormat::Json => {
+            let steps: Vec<_> = steps
+                .iter()
+                .map(|step| {
+                    json!({
+                        "name": step.name,
+                        "ok": step.result.is_ok(),
+                        "error": step.result.as_ref().err().map(|e| e.to_string()),
+                    })
+                })
+                .collect();
+            let ok = steps.iter().all(|step| step["ok"] == true);
+            println!("{}", json!({ "ok": ok, "steps": steps }));
+        }
+    }
+
+    steps
+        .into_iter()
+        .find_map(|step| step.result.err())
+        .map_or(Ok(()), Err)
+}
+
+/// Count down on stdout while the user focuses a text field
+fn wait_for_focus(countdown: u64) {
+    println!(
+        "Focus an empty text field, e.g. a new note, to type \"{}\" into",
+        TEST_TEXT
+    );
+    for remaining in (1..=countdown).rev() {
+        print!("{}... ", remaining);
+        let _ = std::io::stdout().flush();
+        thread::sleep(Duration::from_secs(1));
+    }
+    if countdown > 0 {
+        println!();
+    }
+}
@@ -0,0 +1,501 @@
+//! Long-running command server used by `--daemon` and `--socket`.
+//!
+//! Both modes speak the same line protocol: every request is a single line
+//! holding a regular keystroke command without the binary name, for example
+//! `tap CTRL+S`, `down W` or `type "hello world"`. Arguments are separated by
+//! whitespace and may be wrapped in double or single quotes. Each request is
+//! answered with exactly one line, either `ok` or `error: <message>`. Empty
+//! lines are ignored and receive no response.
+//!
+//! `status` is answered with `ok` followed by a JSON object listing what is
+//! held down, e.g. `ok {"held":[{"key":"Shift","held_ms":1520}]}`.
+//!
+//! With a maximum hold time, keys left pressed longer than that, e.g. because
+//! the client died between `down` and `up`, are released automatically. With a
+//! target window, it is brought to the front before each request. With a
+//! debounce time, a `tap` of the same keys again within it is ignored.
+//!
+//! Socket clients are served concurrently, each on its own thread, so a client
+//! that keeps its connection open doesn't lock out the others. Requests still
+//! run one at a time, in the order they arrive.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use enigo::Enigo;
+
+use crate::{execute, held_status, window, DaemonCommand, Error, Held, KeyCommand, HELD};
+
+/// How the server handles requests
+#[derive(Default)]
+pub struct Options {
+    /// Release keys held down for longer than this
+    pub max_hold: Option<Duration>,
+    /// Window to bring to the front before each request, see `window::focus`
+    pub window: Option<String>,
+    /// Ignore a `tap` of the same keys repeated within this long
+    pub debounce: Option<Duration>,
+}
+
+/// When each key combination was last tapped, for debouncing. Keys are
+/// compared as written, so independent controls don't hold each other back.
+#[derive(Default)]
+struct Debounce {
+    last_tap: HashMap<String, Instant>,
+}
+
+impl Debounce {
+    /// Whether a tap of `key` now should go through, recording it if so
+    fn allow(&mut self, key: &str, window: Duration) -> bool {
+        self.allow_at(key, window, Instant::now())
+    }
+
+    fn allow_at(&mut self, key: &str, window: Duration, now: Instant) -> bool {
+        match self.last_tap.get(key) {
+            Some(last) if now.duration_since(*last) < window => false,
+            _ => {
+                self.last_tap.insert(key.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// What every connection shares: the input device, so requests from different
+/// clients don't interleave, and the debounce times
+struct Shared<'a> {
+    enigo: Mutex<&'a mut Enigo>,
+    debounce: Mutex<Debounce>,
+}
+
+impl<'a> Shared<'a> {
+    fn new(enigo: &'a mut Enigo) -> Self {
+        Self {
+            enigo: Mutex::new(enigo),
+            debounce: Mutex::default(),
+        }
+    }
+
+    /// The input device, for one request. A request that panicked leaves
+    /// nothing half-updated here, so a poisoned lock is taken over as is.
+    fn enigo(&self) -> MutexGuard<'_, &'a mut Enigo> {
+        self.enigo.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn debounce(&self) -> MutexGuard<'_, Debounce> {
+        self.debounce.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Serve commands from stdin until EOF
+pub fn run_stdin(enigo: &mut Enigo, options: &Options) {
+    let stdout = io::stdout();
+    let shared = Shared::new(enigo);
+    let _ = serve(&shared, BufReader::new(io::stdin()), stdout.lock(), options);
+}
+
+/// Serve commands from clients connecting to a Unix domain socket.
+///
+/// Each client is served on its own thread; a disconnect only ends that
+/// client's session.
+#[cfg(unix)]
+pub fn run_socket(enigo: &mut Enigo, path: &Path, options: &Options) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // Clean up a stale socket left behind by a previous run
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("Failed to bind socket '{}': {}", path.display(), e))?;
+    let shared = Shared::new(enigo);
+
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Warning: Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            let reader = match stream.try_clone() {
+                Ok(reader) => BufReader::new(reader),
+                Err(e) => {
+                    eprintln!("Warning: Failed to set up connection: {}", e);
+                    continue;
+                }
+            };
+            let shared = &shared;
+            scope.spawn(move || {
+                if let Err(e) = serve(shared, reader, stream, options) {
+                    eprintln!("Warning: Client connection closed: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// Serve commands from clients connecting to a Windows named pipe.
+///
+/// Paths not already in the `\\.\pipe\` namespace are placed there. Each
+/// client is served on its own thread, with a new pipe instance created for
+/// the next one; a disconnect only ends that client's session.
+#[cfg(windows)]
+pub fn run_socket(enigo: &mut Enigo, path: &Path, options: &Options) -> Result<(), String> {
+    use std::fs::File;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::FromRawHandle;
+    use windows_sys::Win32::Foundation::{
+        GetLastError, ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_DUPLEX;
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    const PIPE_PREFIX: &str = r"\\.\pipe\";
+    const BUFFER_SIZE: u32 = 4096;
+
+    let name = path.to_string_lossy();
+    let name = if name.starts_with(PIPE_PREFIX) {
+        name.into_owned()
+    } else {
+        format!("{}{}", PIPE_PREFIX, name)
+    };
+    let wide_name: Vec<u16> = std::ffi::OsStr::new(&name)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let shared = Shared::new(enigo);
+
+    thread::scope(|scope| loop {
+        // SAFETY: `wide_name` is a valid NUL-terminated UTF-16 string that
+        // outlives the call, and a null security descriptor is allowed.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                wide_name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                std::ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(format!(
+                "Failed to create named pipe '{}': {}",
+                name,
+                io::Error::last_os_error()
+            ));
+        }
+
+        // SAFETY: `handle` is a valid pipe handle created above, and a null
+        // OVERLAPPED pointer requests a blocking connect.
+        let connected = unsafe { ConnectNamedPipe(handle, std::ptr::null_mut()) } != 0
+            || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+
+        // SAFETY: `handle` is owned exclusively by this `File` from here on,
+        // which closes it when dropped.
+        let pipe = unsafe { File::from_raw_handle(handle) };
+        if !connected {
+            eprintln!(
+                "Warning: Failed to accept connection: {}",
+                io::Error::last_os_error()
+            );
+            continue;
+        }
+
+        let reader = match pipe.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(e) => {
+                eprintln!("Warning: Failed to set up connection: {}", e);
+                continue;
+            }
+        };
+        let shared = &shared;
+        scope.spawn(move || {
+            if let Err(e) = serve(shared, reader, pipe, options) {
+                eprintln!("Warning: Client connection closed: {}", e);
+            }
+        });
+    })
+}
+
+/// Execute each line read from `input` and acknowledge it on `output`,
+/// releasing keys held for longer than the maximum hold time in between
+fn serve<R: BufRead + Send + 'static, W: Write>(
+    shared: &Shared,
+    input: R,
+    mut output: W,
+    options: &Options,
+) -> io::Result<()> {
+    // Read on another thread so the watchdog can run while waiting for input
+    let (tx, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let next_release = options
+            .max_hold
+            .and_then(|max_hold| release_overdue(&mut shared.enigo(), max_hold));
+        let line = match next_release {
+            Some(timeout) => match lines.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match lines.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            },
+        };
+
+        let line = line?;
+        let args = split_command_line(&line);
+        if args.is_empty() {
+            continue;
+        }
+
+        log::debug!("Request: {}", line);
+        let result = DaemonCommand::try_parse_from(args)
+            .map_err(|e| Error::Parse(clap_error_message(&e)))
+            .and_then(|cmd| match cmd.command {
+                KeyCommand::Status => Ok(Some(held_status())),
+                KeyCommand::Tap { ref keys, .. }
+                    if options
+                        .debounce
+                        .is_some_and(|window| !shared.debounce().allow(&keys.key, window)) =>
+                {
+                    log::debug!("Ignoring repeated tap of {}", keys.key);
+                    Ok(None)
+                }
+                command => {
+                    // Held until the request is done, so another client's
+                    // input can't land in between
+                    let mut enigo = shared.enigo();
+                    if let Some(target) = &options.window {
+                        window::focus_or_warn(target);
+                    }
+                    execute(&mut enigo, command).map(|()| None)
+                }
+            });
+
+        if let Err(e) = &result {
+            log::debug!("Request failed: {}", e);
+        }
+        match result {
+            Ok(None) => writeln!(output, "ok")?,
+            Ok(Some(status)) => writeln!(output, "ok {}", status)?,
+            Err(e) => writeln!(output, "error: {}", e)?,
+        }
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Release every key held for longer than `max_hold`. Returns how long until
+/// the next held key is due, if any are left.
+fn release_overdue(enigo: &mut Enigo, max_hold: Duration) -> Option<Duration> {
+    let held = HELD.lock().map(|held| held.clone()).unwrap_or_default();
+    let (overdue, next_due) = find_overdue(&held, max_hold, Instant::now());
+
+    for input in overdue {
+        eprintln!(
+            "Warning: Releasing {:?}, held for longer than {}ms",
+            input,
+            max_hold.as_millis()
+        );
+        if let Err(e) = input.release(enigo) {
+            eprintln!("Warning: {}", e);
+            // Don't keep retrying a key that can't be released
+            if let Ok(mut held) = HELD.lock() {
+                held.retain(|(h, _)| *h != input);
+            }
+        }
+    }
+    next_due
+}
+
+/// The inputs held for at least `max_hold` at `now`, and how long until the
+/// next of the others is due
+fn find_overdue(
+    held: &[(Held, Instant)],
+    max_hold: Duration,
+    now: Instant,
+) -> (Vec<Held>, Option<Duration>) {
+    let mut overdue = Vec::new();
+    let mut next_due = None;
+    for &(input, pressed_at) in held {
+        let held_for = now.duration_since(pressed_at);
+        if held_for >= max_hold {
+            overdue.push(input);
+        } else {
+            let due = max_hold - held_for;
+            next_due = Some(next_due.map_or(due, |next: Duration| next.min(due)));
+        }
+    }
+    (overdue, next_due)
+}
+
+/// Split a command line into arguments, honoring double and single quotes
+fn split_command_line(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// Reduce a clap error to its first line, without the "error: " prefix
+fn clap_error_message(error: &clap::Error) -> String {
+    let rendered = error.to_string();
+    let first_line = rendered.lines().next().unwrap_or_default();
+    first_line
+        .strip_prefix("error: ")
+        .unwrap_or(first_line)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounces_repeated_taps() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let window = Duration::from_millis(100);
+        let mut debounce = Debounce::default();
+
+        assert!(debounce.allow_at("W", window, at(0)));
+        // Dropped inside the window, which runs from the last tap let through
+        assert!(!debounce.allow_at("W", window, at(50)));
+        assert!(!debounce.allow_at("W", window, at(99)));
+        assert!(debounce.allow_at("W", window, at(100)));
+        assert!(!debounce.allow_at("W", window, at(150)));
+        assert!(debounce.allow_at("W", window, at(250)));
+    }
+
+    #[test]
+    fn debounces_keys_independently() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let window = Duration::from_millis(100);
+        let mut debounce = Debounce::default();
+
+        assert!(debounce.allow_at("W", window, at(0)));
+        assert!(debounce.allow_at("S", window, at(10)));
+        // Written differently, so not the same control
+        assert!(debounce.allow_at("CTRL+W", window, at(20)));
+        assert!(!debounce.allow_at("W", window, at(30)));
+        assert!(!debounce.allow_at("S", window, at(30)));
+    }
+
+    #[test]
+    fn finds_keys_held_too_long() {
+        use enigo::{Button, Key};
+
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let max_hold = Duration::from_millis(1000);
+        let held = [
+            (Held::Key(Key::Shift), at(0)),
+            (Held::Key(Key::Unicode('w')), at(700)),
+            (Held::Button(Button::Left), at(200)),
+            (Held::Raw(17), at(900)),
+        ];
+
+        let (overdue, next_due) = find_overdue(&held, max_hold, at(1200));
+        assert_eq!(overdue, [Held::Key(Key::Shift), Held::Button(Button::Left)]);
+        // W is due next, 500ms before the raw key
+        assert_eq!(next_due, Some(Duration::from_millis(500)));
+
+        // Exactly at the limit counts as overdue
+        let (overdue, next_due) = find_overdue(&held[1..2], max_hold, at(1700));
+        assert_eq!(overdue, [Held::Key(Key::Unicode('w'))]);
+        assert_eq!(next_due, None);
+
+        assert_eq!(find_overdue(&[], max_hold, at(0)), (vec![], None));
+    }
+
+    #[test]
+    fn splits_command_lines() {
+        assert_eq!(split_command_line("tap CTRL+S"), ["tap", "CTRL+S"]);
+        assert_eq!(split_command_line("  down\tW  "), ["down", "W"]);
+        assert_eq!(
+            split_command_line(r#"type "hello world" --delay 5"#),
+            ["type", "hello world", "--delay", "5"]
+        );
+        assert_eq!(
+            split_command_line(r#"type 'say "hi"'"#),
+            ["type", r#"say "hi""#]
+        );
+        // Quotes join with the text around them, and can be empty
+        assert_eq!(split_command_line(r#"type a"b c"d"#), ["type", "ab cd"]);
+        assert_eq!(split_command_line(r#"type """#), ["type", ""]);
+    }
+
+    #[test]
+    fn keeps_backslashes_for_the_key_parser() {
+        assert_eq!(split_command_line(r"tap CTRL+\,"), ["tap", r"CTRL+\,"]);
+        assert_eq!(split_command_line(r#"type "a\nb""#), ["type", r"a\nb"]);
+    }
+
+    #[test]
+    fn empty_and_unterminated_lines() {
+        assert!(split_command_line("").is_empty());
+        assert!(split_command_line("   \t").is_empty());
+        // An unclosed quote runs to the end of the line
+        assert_eq!(
+            split_command_line(r#"type "open ended"#),
+            ["type", "open ended"]
+        );
+    }
+}
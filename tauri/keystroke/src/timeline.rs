@@ -0,0 +1,275 @@
+//! JSON timelines for `keystroke play`.
+//!
+//! A timeline is an array of steps executed in order on one Enigo instance:
+//!
+//! ```json
+//! [
+//!     { "action": "tap", "key": "CTRL+S" },
+//!     { "action": "type", "text": "1234", "delay_before": 250 },
+//!     { "action": "down", "key": "W" },
+//!     { "action": "up", "key": "W", "delay_before": 2000 },
+//!     { "action": "scroll", "axis": "vertical", "amount": -3 }
+//! ]
+//! ```
+//!
+//! `delay_before` is optional and given in milliseconds. Steps without one
+//! after the first wait `KEYSTROKE_DELAY_MS` if it is set.
+//!
+//! `keystroke run` takes the same kind of steps inline, limited to typed text
+//! and key taps: `text:"1234",key:ENTER,key:CTRL+S`.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::keys::{ParseOptions, DEFAULT_SEPARATOR};
+use crate::{parse_combos, Edge, KeyArgs, KeyCommand, ParseArgs, ScrollAxis, ScrollUnit};
+
+#[derive(Deserialize)]
+pub struct Step {
+    /// Pause before running this step, in milliseconds
+    #[serde(default)]
+    pub delay_before: Option<u64>,
+
+    #[serde(flatten)]
+    pub action: StepAction,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum StepAction {
+    Tap {
+        key: String,
+    },
+    Down {
+        key: String,
+    },
+    Up {
+        key: String,
+    },
+    Type {
+        text: String,
+    },
+    Scroll {
+        axis: ScrollAxis,
+        amount: i32,
+        #[serde(default)]
+        unit: ScrollUnit,
+    },
+}
+
+impl StepAction {
+    /// The CLI command equivalent to this step
+    pub fn into_command(self, parse: &ParseArgs) -> KeyCommand {
+        match self {
+            StepAction::Tap { key } => KeyCommand::Tap {
+                keys: KeyArgs::new(key, parse.clone()),
+                hold: None,
+                repeat: 1,
+                repeat_interval: 0,
+                reset_modifiers: false,
+                edge: Edge::Press,
+            },
+            StepAction::Down { key } => KeyCommand::Down {
+                keys: KeyArgs::new(key, parse.clone()),
+                hold: None,
+            },
+            StepAction::Up { key } => KeyCommand::Up {
+                keys: KeyArgs::new(key, parse.clone()),
+            },
+            StepAction::Type { text } => KeyCommand::Type {
+                text,
+                delay: None,
+                literal: false,
+                with: None,
+                verify: false,
+            },
+            StepAction::Scroll { axis, amount, unit } => KeyCommand::Scroll { axis, amount, unit },
+        }
+    }
+}
+
+/// Read and parse a timeline file, validating every key before anything is sent
+pub fn load(path: &Path, options: &ParseOptions) -> Result<Vec<Step>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let steps: Vec<Step> = serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid timeline '{}': {}", path.display(), e))?;
+
+    for (i, step) in steps.iter().enumerate() {
+        if let StepAction::Tap { key } | StepAction::Down { key } | StepAction::Up { key } =
+            &step.action
+        {
+            parse_combos(key, options).map_err(|e| format!("Step {}: {}", i + 1, e))?;
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Parse an inline sequence for `keystroke run`, validating every key before
+/// anything is sent. Steps are separated by commas; text may be wrapped in
+/// double or single quotes to include commas, with `\` escaping the quote.
+/// Keys may include a comma key escaped as `\,`.
+pub fn parse_sequence(sequence: &str, options: &ParseOptions) -> Result<Vec<StepAction>, String> {
+    let mut steps = Vec::new();
+    let mut rest = sequence.trim_start();
+
+    while !rest.is_empty() {
+        let n = steps.len() + 1;
+        let (kind, value) = rest
+            .split_once(':')
+            .ok_or_else(|| format!("Step {}: expected 'text:' or 'key:' before '{}'", n, rest))?;
+        let kind = kind.trim();
+
+        let (value, remaining) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) if kind == "text" => {
+                let (text, after) = unquote(&value[1..], quote)
+                    .ok_or_else(|| format!("Step {}: unterminated quoted text", n))?;
+                let after = after.trim_start();
+                let after = match after.strip_prefix(',') {
+                    Some(after) => after,
+                    None if after.is_empty() => after,
+                    None => return Err(format!("Step {}: expected ',' after the quoted text", n)),
+                };
+                (text, after)
+            }
+            _ if kind == "key" => split_key_step(value, options),
+            _ => match value.split_once(',') {
+                Some((value, after)) => (value.to_string(), after),
+                None => (value.to_string(), ""),
+            },
+        };
+
+        let action = match kind {
+            "text" => StepAction::Type { text: value },
+            "key" => {
+                let key = value.trim().to_string();
+                parse_combos(&key, options).map_err(|e| format!("Step {}: {}", n, e))?;
+                StepAction::Tap { key }
+            }
+            _ => {
+                return Err(format!(
+                    "Step {}: unknown step '{}', expected 'text' or 'key'",
+                    n, kind
+                ))
+            }
+        };
+        steps.push(action);
+        rest = remaining.trim_start();
+    }
+
+    Ok(steps)
+}
+
+/// Read the keys of a `key:` step up to the next comma that isn't escaped as
+/// `\,`, returning them and what follows. With the default separator the
+/// escape is kept for `parse_combos` to resolve; with `--sep` a comma is no
+/// separator there, so it is unescaped here.
+fn split_key_step<'a>(input: &'a str, options: &ParseOptions) -> (String, &'a str) {
+    let keep_escape = options.separator.unwrap_or(DEFAULT_SEPARATOR) == ',';
+    let mut keys = String::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, next)| *next == ',') => {
+                chars.next();
+                keys.push_str(if keep_escape { "\\," } else { "," });
+            }
+            ',' => return (keys, &input[i + 1..]),
+            c => keys.push(c),
+        }
+    }
+    (keys, "")
+}
+
+/// Read quoted text up to the closing `quote`, returning it and what follows
+fn unquote(input: &str, quote: char) -> Option<(String, &str)> {
+    let mut text = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next()?.1),
+            c if c == quote => return Some((text, &input[i + c.len_utf8()..])),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(steps: &[StepAction]) -> Vec<String> {
+        steps
+            .iter()
+            .map(|step| match step {
+                StepAction::Type { text } => format!("text {}", text),
+                StepAction::Tap { key } => format!("key {}", key),
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sequence_of_text_and_keys() {
+        let steps = parse_sequence(
+            r#"text:"12,34", key:ENTER,key:CTRL+S"#,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(kinds(&steps), ["text 12,34", "key ENTER", "key CTRL+S"]);
+    }
+
+    #[test]
+    fn sequence_quotes_and_escapes() {
+        let steps = parse_sequence(
+            r#"text:'say "hi"',text:"a\"b",text:plain"#,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            kinds(&steps),
+            [r#"text say "hi""#, r#"text a"b"#, "text plain"]
+        );
+    }
+
+    #[test]
+    fn sequence_escaped_comma() {
+        let steps = parse_sequence(r"key:CTRL+\,,text:x", &ParseOptions::default()).unwrap();
+        assert_eq!(kinds(&steps), [r"key CTRL+\,", "text x"]);
+        let combos = parse_combos(r"CTRL+\,", &ParseOptions::default()).unwrap();
+        assert_eq!(combos.len(), 1);
+        assert_eq!(combos[0].1, Some(enigo::Key::Unicode(',')));
+    }
+
+    #[test]
+    fn sequence_with_separator() {
+        let options = ParseOptions {
+            separator: Some(';'),
+            ..ParseOptions::default()
+        };
+        let steps = parse_sequence(r"key:CTRL+S;ENTER,key:CTRL+\,;TAB", &options).unwrap();
+        assert_eq!(kinds(&steps), ["key CTRL+S;ENTER", "key CTRL+,;TAB"]);
+        let combos = parse_combos("CTRL+,;TAB", &options).unwrap();
+        assert_eq!(combos.len(), 2);
+        assert_eq!(combos[0].1, Some(enigo::Key::Unicode(',')));
+    }
+
+    #[test]
+    fn sequence_errors() {
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let error = |sequence| match parse_sequence(sequence, &strict) {
+            Ok(_) => panic!("{} parsed", sequence),
+            Err(e) => e,
+        };
+        assert!(error("key:CTRL+S,ENTER").starts_with("Step 2: expected"));
+        assert!(error("press:W").contains("unknown step 'press'"));
+        assert!(error(r#"text:"open"#).contains("unterminated"));
+        assert!(error("key:SHFT+S").contains("Unknown key 'SHFT'"));
+    }
+}
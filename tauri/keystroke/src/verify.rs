@@ -0,0 +1,82 @@
+//! `type --verify`: check that typed text reached the focused field.
+//!
+//! The field is read back by selecting all of it and copying it, so this only
+//! works in text fields that support both shortcuts, and compares the whole
+//! field. Whatever was on the clipboard is put back afterwards; on X11 it only
+//! survives this process exiting if a clipboard manager takes it over.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use arboard::Clipboard;
+use enigo::{Direction, Enigo, Key};
+
+use crate::{send_key, Error};
+
+/// How long the target gets to put the copied text on the clipboard
+const COPY_TIMEOUT: Duration = Duration::from_millis(500);
+const COPY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Modifier of the select-all and copy shortcuts
+#[cfg(target_os = "macos")]
+const SHORTCUT_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const SHORTCUT_MODIFIER: Key = Key::Control;
+
+/// Read the focused field back and fail if it doesn't hold `expected`
+pub fn verify_typed(enigo: &mut Enigo, expected: &str) -> Result<(), Error> {
+    let unavailable = |e: arboard::Error| Error::Send(format!("Can't verify, no clipboard: {}", e));
+    let mut clipboard = Clipboard::new().map_err(unavailable)?;
+    let previous = clipboard.get_text().ok();
+    // So a copy that never happens isn't mistaken for the field's contents
+    clipboard.clear().map_err(unavailable)?;
+
+    let result = copy_field(enigo).map(|()| read_copied(&mut clipboard));
+    if let Some(previous) = previous {
+        if let Err(e) = clipboard.set_text(previous) {
+            eprintln!("Warning: Could not restore the clipboard: {}", e);
+        }
+    }
+
+    match result? {
+        None => Err(Error::Send(
+            "Could not verify the text, copying the focused field put nothing on the clipboard"
+                .to_string(),
+        )),
+        Some(actual) if normalize(&actual) == normalize(expected) => {
+            log::debug!("Verified {:?} in the focused field", expected);
+            Ok(())
+        }
+        Some(actual) => Err(Error::Send(format!(
+            "Typed text did not arrive as sent: expected {:?}, the focused field holds {:?}",
+            expected, actual
+        ))),
+    }
+}
+
+/// Select everything in the focused field and copy it
+fn copy_field(enigo: &mut Enigo) -> Result<(), Error> {
+    send_key(enigo, SHORTCUT_MODIFIER, Direction::Press)?;
+    // Release the modifier even if a shortcut key fails
+    let result = send_key(enigo, Key::Unicode('a'), Direction::Click)
+        .and_then(|()| send_key(enigo, Key::Unicode('c'), Direction::Click));
+    let release = send_key(enigo, SHORTCUT_MODIFIER, Direction::Release);
+    result.and(release)
+}
+
+/// Wait for the copied text to show up on the clipboard
+fn read_copied(clipboard: &mut Clipboard) -> Option<String> {
+    let started = Instant::now();
+    while started.elapsed() < COPY_TIMEOUT {
+        match clipboard.get_text() {
+            Ok(text) if !text.is_empty() => return Some(text),
+            _ => thread::sleep(COPY_POLL_INTERVAL),
+        }
+    }
+    None
+}
+
+/// Compare line endings as typed, since fields may store Return as "\r\n"
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
@@ -0,0 +1,275 @@
+//! `--window`: bring the window input is meant for to the front first.
+//!
+//! Synthetic input always goes to the focused window, so targeting one means
+//! activating it. This is best effort: window managers and Windows' foreground
+//! lock rules may refuse, and macOS and Wayland have no way to do it at all.
+
+use std::thread;
+use std::time::Duration;
+
+use crate::Error;
+
+/// Time the window manager gets to hand focus over before input is sent
+const FOCUS_SETTLE: Duration = Duration::from_millis(50);
+
+/// Activate the window with this title, or this id if it is a decimal or
+/// 0x-prefixed hex number. Titles match exactly, or else the first window
+/// whose title contains the text. Fails with [`Error::NotFound`] if there is
+/// no such window.
+pub fn focus(target: &str) -> Result<(), Error> {
+    platform::focus(target, parse_id(target))?;
+    thread::sleep(FOCUS_SETTLE);
+    Ok(())
+}
+
+/// Activate the window, warning and carrying on with whatever has focus if that fails
+pub fn focus_or_warn(target: &str) {
+    if let Err(e) = focus(target) {
+        warn_unfocused(target, &e);
+    }
+}
+
+/// Activate the window if it exists, warning and carrying on with whatever
+/// has focus if the platform refuses to activate it
+pub fn focus_existing(target: &str) -> Result<(), Error> {
+    match focus(target) {
+        Err(e @ Error::NotFound(_)) => Err(e),
+        Err(e) => {
+            warn_unfocused(target, &e);
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+fn warn_unfocused(target: &str, error: &Error) {
+    eprintln!(
+        "Warning: Could not target window '{}' ({}), sending to the focused window",
+        target, error
+    );
+}
+
+/// The error for a window that doesn't exist
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+fn not_found(what: String) -> Error {
+    Error::NotFound(format!("No window {}", what))
+}
+
+/// Read a window id, as printed by `xwininfo` or `xdotool` on X11, or a
+/// window handle on Windows
+fn parse_id(target: &str) -> Option<u64> {
+    match target
+        .strip_prefix("0x")
+        .or_else(|| target.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None if target.chars().all(|c| c.is_ascii_digit()) => target.parse().ok(),
+        None => None,
+    }
+}
+
+/// Pick the window whose title is `title`, or else the first containing it
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+fn best_match<W: Copy>(windows: &[(W, String)], title: &str) -> Option<W> {
+    windows
+        .iter()
+        .find(|(_, name)| name == title)
+        .or_else(|| windows.iter().find(|(_, name)| name.contains(title)))
+        .map(|(window, _)| *window)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window};
+
+    use crate::Error;
+
+    /// Ask the window manager to activate the window through `_NET_ACTIVE_WINDOW`
+    pub fn focus(target: &str, id: Option<u64>) -> Result<(), Error> {
+        let error = |e: &dyn std::fmt::Display| Error::Send(format!("X11 error: {}", e));
+        let (conn, screen) = x11rb::connect(None).map_err(|e| {
+            Error::Send(if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                "targeting a window is not supported on Wayland".to_string()
+            } else {
+                format!("Failed to connect to X11: {}", e)
+            })
+        })?;
+        let root = conn.setup().roots[screen].root;
+        let atom = |name: &str| -> Result<u32, Error> {
+            Ok(conn
+                .intern_atom(false, name.as_bytes())
+                .map_err(|e| error(&e))?
+                .reply()
+                .map_err(|e| error(&e))?
+                .atom)
+        };
+
+        let window = match id {
+            Some(id) => Window::try_from(id).map_err(|_| format!("Invalid window id {}", id))?,
+            None => {
+                let client_list = conn
+                    .get_property(
+                        false,
+                        root,
+                        atom("_NET_CLIENT_LIST")?,
+                        AtomEnum::WINDOW,
+                        0,
+                        u32::MAX,
+                    )
+                    .map_err(|e| error(&e))?
+                    .reply()
+                    .map_err(|e| error(&e))?;
+                let net_wm_name = atom("_NET_WM_NAME")?;
+                let utf8_string = atom("UTF8_STRING")?;
+
+                let mut windows = Vec::new();
+                for window in client_list.value32().into_iter().flatten() {
+                    let mut name = title(&conn, window, net_wm_name, utf8_string);
+                    if name.is_empty() {
+                        name = title(
+                            &conn,
+                            window,
+                            AtomEnum::WM_NAME.into(),
+                            AtomEnum::STRING.into(),
+                        );
+                    }
+                    windows.push((window, name));
+                }
+                super::best_match(&windows, target)
+                    .ok_or_else(|| super::not_found(format!("titled '{}'", target)))?
+            }
+        };
+
+        // Source indication 2 marks the request as coming from a pager-like
+        // tool acting for the user, which window managers honor more readily
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            atom("_NET_ACTIVE_WINDOW")?,
+            [2, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )
+        .map_err(|e| error(&e))?;
+        conn.flush().map_err(|e| error(&e))?;
+        Ok(())
+    }
+
+    /// A window's title from the given property, or "" if it has none
+    fn title(conn: &impl Connection, window: Window, property: u32, kind: u32) -> String {
+        conn.get_property(false, window, property, kind, 0, 1024)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::Foundation::{HWND, LPARAM};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowTextW, IsIconic, IsWindow, IsWindowVisible, SetForegroundWindow,
+        ShowWindow, SW_RESTORE,
+    };
+
+    use crate::Error;
+
+    /// Restore the window if it is minimized and make it the foreground window
+    pub fn focus(target: &str, id: Option<u64>) -> Result<(), Error> {
+        let window = match id {
+            Some(id) => id as usize as HWND,
+            None => {
+                let windows = visible_windows();
+                super::best_match(&windows, target)
+                    .ok_or_else(|| super::not_found(format!("titled '{}'", target)))?
+            }
+        };
+
+        // SAFETY: these calls only read the handle, and fail gracefully if it
+        // doesn't refer to a window
+        unsafe {
+            if IsWindow(window) == 0 {
+                return Err(super::not_found(format!("with handle {:?}", window)));
+            }
+            if IsIconic(window) != 0 {
+                ShowWindow(window, SW_RESTORE);
+            }
+            if SetForegroundWindow(window) == 0 {
+                return Err(Error::Send(
+                    "Windows refused to bring the window to the front".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every visible top-level window with its title
+    fn visible_windows() -> Vec<(HWND, String)> {
+        unsafe extern "system" fn collect(window: HWND, windows: LPARAM) -> BOOL {
+            // SAFETY: `windows` is the pointer to the Vec passed to EnumWindows
+            // below, which outlives the enumeration
+            let windows = unsafe { &mut *(windows as *mut Vec<(HWND, String)>) };
+            let mut buf = [0u16; 512];
+            // SAFETY: `buf` is valid for the length passed
+            let len = unsafe {
+                if IsWindowVisible(window) == 0 {
+                    return 1;
+                }
+                GetWindowTextW(window, buf.as_mut_ptr(), buf.len() as i32)
+            };
+            if len > 0 {
+                windows.push((window, String::from_utf16_lossy(&buf[..len as usize])));
+            }
+            1
+        }
+
+        let mut windows: Vec<(HWND, String)> = Vec::new();
+        // SAFETY: the callback only uses the pointer during the call
+        unsafe {
+            EnumWindows(Some(collect), &mut windows as *mut _ as LPARAM);
+        }
+        windows
+    }
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+mod platform {
+    use crate::Error;
+
+    pub fn focus(_target: &str, _id: Option<u64>) -> Result<(), Error> {
+        Err(Error::Send(
+            "targeting a window is not supported on this platform".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_titles() {
+        assert_eq!(parse_id("0x3a00004"), Some(0x3a00004));
+        assert_eq!(parse_id("12345"), Some(12345));
+        assert_eq!(parse_id("Train Sim World 5"), None);
+
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        {
+            let windows = [
+                (1, "Trenino".to_string()),
+                (2, "Train Sim World 5".to_string()),
+                (3, "Train Sim World".to_string()),
+            ];
+            assert_eq!(best_match(&windows, "Train Sim World"), Some(3));
+            assert_eq!(best_match(&windows, "World 5"), Some(2));
+            assert_eq!(best_match(&windows, "Steam"), None);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Capture of the backend sidecar's output.
+//!
+//! Every line is appended to `backend.log` in the app log directory, which is
+//! rotated on each launch, and the most recent lines are kept in memory so a
+//! startup failure can show what went wrong.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+const LOG_FILE_NAME: &str = "backend.log";
+/// Logs from this many previous launches are kept as backend.log.1, .2, ...
+const ROTATED_LOGS: u32 = 3;
+/// Lines of recent output kept in memory, for the error dialog and bug reports
+const DEFAULT_RECENT_LINES: usize = 200;
+/// Set to a number of lines to keep more or less of the recent output
+const RECENT_LINES_ENV: &str = "TRENINO_LOG_BUFFER_LINES";
+/// Most lines TRENINO_LOG_BUFFER_LINES may ask for, bounding the memory used
+const MAX_RECENT_LINES: usize = 100_000;
+
+/// Which pipe a line of output came from
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// State to hold the backend log file and the most recent output lines
+pub struct BackendLog {
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+    recent: Mutex<VecDeque<String>>,
+    /// Lines `recent` holds at most
+    capacity: usize,
+}
+
+impl BackendLog {
+    /// Rotate previous logs and start a new one in the app log directory.
+    /// Output is still kept in memory if the file can't be created.
+    pub fn open(handle: &tauri::AppHandle) -> Self {
+        let (path, file) = match handle.path().app_log_dir() {
+            Ok(dir) => match create_log_file(&dir) {
+                Ok(file) => {
+                    let path = dir.join(LOG_FILE_NAME);
+                    log::info!("Writing backend output to {}", path.display());
+                    (Some(path), Some(file))
+                }
+                Err(e) => {
+                    log::warn!("Could not create backend log in {}: {}", dir.display(), e);
+                    (None, None)
+                }
+            },
+            Err(e) => {
+                log::warn!("Could not resolve the app log directory: {}", e);
+                (None, None)
+            }
+        };
+
+        let capacity = recent_lines_capacity();
+        Self {
+            path,
+            file: Mutex::new(file),
+            recent: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Path of the current log file, if it could be created
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record one line of backend output
+    pub fn record(&self, stream: Stream, line: &str) {
+        // Already kept in the backend log, so only repeated in the app log at
+        // debug level, e.g. when TRENINO_DEV is set
+        match stream {
+            Stream::Stdout => log::debug!(target: "backend", "{}", line),
+            Stream::Stderr => log::debug!(target: "backend", "stderr: {}", line),
+        }
+
+        if let Ok(mut guard) = self.file.lock() {
+            if let Some(file) = guard.as_mut() {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == self.capacity {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_string());
+        }
+    }
+
+    /// The most recent lines of output, oldest first
+    pub fn recent_lines(&self) -> Vec<String> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Lines of output to keep in memory, from RECENT_LINES_ENV or the default
+fn recent_lines_capacity() -> usize {
+    let Ok(value) = std::env::var(RECENT_LINES_ENV) else {
+        return DEFAULT_RECENT_LINES;
+    };
+    match value.trim().parse() {
+        Ok(lines @ 1..=MAX_RECENT_LINES) => lines,
+        _ => {
+            log::warn!(
+                "Invalid {} '{}', expected 1 to {}, keeping {} lines",
+                RECENT_LINES_ENV,
+                value,
+                MAX_RECENT_LINES,
+                DEFAULT_RECENT_LINES
+            );
+            DEFAULT_RECENT_LINES
+        }
+    }
+}
+
+fn create_log_file(dir: &Path) -> std::io::Result<File> {
+    fs::create_dir_all(dir)?;
+
+    // backend.log.2 -> backend.log.3, backend.log.1 -> backend.log.2, ...
+    for n in (1..ROTATED_LOGS).rev() {
+        let _ = fs::rename(rotated_path(dir, n), rotated_path(dir, n + 1));
+    }
+    let _ = fs::rename(dir.join(LOG_FILE_NAME), rotated_path(dir, 1));
+
+    File::create(dir.join(LOG_FILE_NAME))
+}
+
+fn rotated_path(dir: &Path, n: u32) -> PathBuf {
+    dir.join(format!("{}.{}", LOG_FILE_NAME, n))
+}
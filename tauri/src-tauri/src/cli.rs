@@ -0,0 +1,88 @@
+//! Command line flags of the launcher.
+//!
+//! The launcher is configured through environment variables, and each flag is
+//! a shorthand that sets one of them for this launch. Arguments that aren't
+//! recognized are ignored, so whatever the OS or Tauri pass still works.
+
+use clap::error::ErrorKind;
+use clap::Parser;
+
+use crate::{BACKEND_ENV_ENV, BACKEND_PORT_ENV, DEV_ENV, EXTERNAL_BACKEND_ENV, HEADLESS_ENV};
+
+const ENV_HELP: &str = "\
+Environment variables:
+  TRENINO_BACKEND_PORT            Port for the backend (same as --port)
+  TRENINO_BACKEND_HOST            Host the backend is reached on [default: localhost]
+  TRENINO_EXTERNAL_BACKEND=1      Use an already running backend (same as --external-backend)
+  TRENINO_DEV=1                   Open the devtools and log at debug level (same as --dev)
+  TRENINO_BACKEND_ENV             prod for the bundled release, dev for a mix build [default: prod]
+  TRENINO_BACKEND_TIMEOUT_MS      How long to wait for the backend to start [default: 120000]
+  TRENINO_BACKEND_RETRY_DELAY_MS  Longest wait between readiness checks [default: 1000]
+  TRENINO_HEALTH_METHOD           GET or HEAD for the readiness check [default: GET]
+  TRENINO_HEALTH_STATUS           Status the readiness check must answer with [default: any 2xx]
+  TRENINO_BACKEND_RESTART=0       Don't restart the backend if it crashes
+  TRENINO_WINDOW_WIDTH            Initial window width [default: 1200, min: 800]
+  TRENINO_WINDOW_HEIGHT           Initial window height [default: 800, min: 600]
+  TRENINO_WINDOW_TITLE            Title of the main window [default: Trenino]
+  TRENINO_HEADLESS=1              Run the backend without a window (same as --headless)
+  TRENINO_LOG_BUFFER_LINES        Lines of backend output kept for error reports [default: 200]
+  TRENINO_NO_MOUSE=1              Don't let the keystroke tool send mouse input
+  TRENINO_SIMULATE=1              Print what startup would do and exit without starting anything";
+
+#[derive(Parser)]
+#[command(name = "trenino", version)]
+#[command(about = "Bridge hardware to Train Sim World", long_about = None)]
+#[command(after_help = ENV_HELP)]
+#[command(ignore_errors = true)]
+struct Cli {
+    /// Start the backend on this port instead of picking a free one
+    #[arg(long, value_name = "PORT")]
+    port: Option<u16>,
+
+    /// Use a backend that is already running instead of starting one
+    #[arg(long)]
+    external_backend: bool,
+
+    /// Open the devtools and log at debug level
+    #[arg(long)]
+    dev: bool,
+
+    /// Run the backend without a window, keeping it supervised in the background
+    #[arg(long)]
+    headless: bool,
+
+    /// Start the backend as a dev build (MIX_ENV=dev) instead of the release
+    #[arg(long, value_name = "ENV", value_parser = ["prod", "dev"])]
+    backend_env: Option<String>,
+}
+
+/// Handle `--help` and `--version`, which exit, and turn the other flags into
+/// the environment variables they stand for. Must run before any threads start.
+pub fn apply() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+            e.exit()
+        }
+        Err(e) => {
+            eprintln!("Ignoring command line arguments: {}", e);
+            return;
+        }
+    };
+
+    if let Some(port) = cli.port {
+        std::env::set_var(BACKEND_PORT_ENV, port.to_string());
+    }
+    if cli.external_backend {
+        std::env::set_var(EXTERNAL_BACKEND_ENV, "1");
+    }
+    if cli.dev {
+        std::env::set_var(DEV_ENV, "1");
+    }
+    if cli.headless {
+        std::env::set_var(HEADLESS_ENV, "1");
+    }
+    if let Some(env) = cli.backend_env {
+        std::env::set_var(BACKEND_ENV_ENV, env);
+    }
+}
@@ -1,164 +1,1728 @@
-use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri::async_runtime::Receiver;
+#[cfg(desktop)]
+use tauri::menu::{Menu, MenuItem};
+#[cfg(desktop)]
+use tauri::tray::TrayIconBuilder;
+use tauri::ipc::CapabilityBuilder;
+use tauri::{Emitter, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_log::{Target, TargetKind};
+use tauri_plugin_opener::OpenerExt;
 use tauri_plugin_shell::ShellExt;
-use tauri_plugin_shell::process::CommandChild;
-use std::sync::Mutex;
-use std::time::Duration;
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use std::net::{IpAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-const BACKEND_PORT: u16 = 4000;
-const MAX_RETRIES: u32 = 120; // 2 minutes max wait
-const RETRY_DELAY_MS: u64 = 500;
+mod backend_log;
+#[cfg(desktop)]
+mod cli;
+mod serial_ports;
 
-/// State to hold the backend sidecar process handle for cleanup on exit
-struct BackendProcess(Mutex<Option<CommandChild>>);
+use backend_log::{BackendLog, Stream};
 
-/// Check if the backend is fully ready (migrations complete) by checking health endpoint
-fn check_backend_ready() -> Result<bool, String> {
-    let url = format!("http://localhost:{}/api/health", BACKEND_PORT);
-    match reqwest::blocking::get(&url) {
-        Ok(response) => {
-            if response.status().is_success() {
-                Ok(true)
-            } else {
-                // Server is up but not ready (e.g., migrations running)
-                Ok(false)
+const DEFAULT_BACKEND_PORT: u16 = 4000;
+const BACKEND_PORT_ENV: &str = "TRENINO_BACKEND_PORT";
+const DEFAULT_BACKEND_HOST: &str = "localhost";
+/// Host the backend is reached on, for a backend in a container or on
+/// another machine. The sidecar then listens on all interfaces.
+const BACKEND_HOST_ENV: &str = "TRENINO_BACKEND_HOST";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+/// Overrides STARTUP_TIMEOUT, in milliseconds
+const STARTUP_TIMEOUT_ENV: &str = "TRENINO_BACKEND_TIMEOUT_MS";
+/// Health checks start quickly and back off exponentially up to the max delay
+const INITIAL_RETRY_DELAY_MS: u64 = 50;
+const MAX_RETRY_DELAY_MS: u64 = 1000;
+/// Each wait between health checks is lengthened by up to this fraction, so
+/// services starting together at boot don't poll in lockstep
+const RETRY_JITTER: f64 = 0.1;
+/// Overrides MAX_RETRY_DELAY_MS
+const MAX_RETRY_DELAY_ENV: &str = "TRENINO_BACKEND_RETRY_DELAY_MS";
+/// Longest a single health check may take, so a stalled connection can't
+/// hold up the readiness loop
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_millis(500);
+/// GET or HEAD, for a proxy in front of the backend that only answers HEAD
+const HEALTH_METHOD_ENV: &str = "TRENINO_HEALTH_METHOD";
+/// Status code the health check must answer with, instead of any 2xx
+const HEALTH_STATUS_ENV: &str = "TRENINO_HEALTH_STATUS";
+/// Lines of backend output shown in the startup error dialog
+const DIALOG_LOG_LINES: usize = 10;
+/// How many times to spawn the backend on a fresh port if it exits during
+/// startup or reports that its port is taken
+const MAX_SPAWN_ATTEMPTS: u32 = 3;
+/// Set to 0 or false to leave the backend down if it crashes mid-session
+const RESTART_ENV: &str = "TRENINO_BACKEND_RESTART";
+/// Crash restarts allowed per session, waiting longer before each one
+const MAX_RESTARTS: u32 = 5;
+const INITIAL_RESTART_DELAY_MS: u64 = 1000;
+const MAX_RESTART_DELAY_MS: u64 = 30_000;
+/// Set to 1 to open the devtools and log at debug level
+const DEV_ENV: &str = "TRENINO_DEV";
+/// Launcher log in the app log directory, next to the backend log
+const LOG_FILE_NAME: &str = "trenino";
+/// Set to 1 to always use a backend started separately instead of the sidecar
+const EXTERNAL_BACKEND_ENV: &str = "TRENINO_EXTERNAL_BACKEND";
+/// How long the backend gets to exit on its own before it is killed
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
+/// Set to 1 to run the backend without any window, e.g. on a dedicated IO
+/// box reached over the network. The tray icon stays to open the UI in a
+/// browser and to quit.
+const HEADLESS_ENV: &str = "TRENINO_HEADLESS";
+/// Set to 1 to log what the launcher would do and exit without starting the
+/// backend or opening a window, e.g. to test a package on a headless CI runner
+const SIMULATE_ENV: &str = "TRENINO_SIMULATE";
+/// Set to 1 to stop the keystroke tool the backend runs from sending mouse
+/// input, where only synthetic keyboard input is allowed
+const NO_MOUSE_ENV: &str = "TRENINO_NO_MOUSE";
+/// Set to dev to run a backend built with `mix` instead of the bundled
+/// Burrito release, e.g. a sidecar wrapper around `mix phx.server`
+const BACKEND_ENV_ENV: &str = "TRENINO_BACKEND_ENV";
+/// Name of the Elixir backend sidecar binary
+const BACKEND_SIDECAR: &str = "trenino_backend";
+/// The other bundled sidecars and the arguments that make each print its
+/// version. The backend reports its own through the health endpoint instead,
+/// since running it starts the whole app.
+const VERSIONED_SIDECARS: &[(&str, &[&str])] =
+    &[("keystroke", &["--version"]), ("avrdude", &["-?"])];
+/// Initial size of the main window, before the window state plugin restores
+/// the last one, and the smallest size the UI works at
+const WINDOW_SIZE: (f64, f64) = (1200.0, 800.0);
+const MIN_WINDOW_SIZE: (f64, f64) = (800.0, 600.0);
+/// Override the initial window size, in logical pixels
+const WINDOW_WIDTH_ENV: &str = "TRENINO_WINDOW_WIDTH";
+const WINDOW_HEIGHT_ENV: &str = "TRENINO_WINDOW_HEIGHT";
+/// Title of the main window, unless overridden or set by the UI
+const WINDOW_TITLE: &str = "Trenino";
+const WINDOW_TITLE_ENV: &str = "TRENINO_WINDOW_TITLE";
+/// Window sizes above this are treated as typos and ignored
+const MAX_WINDOW_DIMENSION: f64 = 10_000.0;
+
+/// State of the backend the window talks to, for everything that controls it:
+/// restarts, the tray menu and stopping it on exit
+struct BackendState(Mutex<Backend>);
+
+struct Backend {
+    /// The sidecar process, for stopping it. None once it has exited, or if
+    /// the backend was started outside Trenino
+    child: Option<CommandChild>,
+    /// Port the backend listens on
+    port: u16,
+    status: BackendStatus,
+    /// Set when the window is attached to a backend Trenino didn't start,
+    /// which is then neither supervised nor stopped on exit
+    external: bool,
+}
+
+/// Where the backend is in its lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BackendStatus {
+    /// Spawned and not answering health checks yet
+    Starting,
+    /// Answered a health check
+    Running,
+    /// Exited on request or after a crash, and about to be started again
+    Restarting,
+    /// Not started yet, or exited and not coming back
+    Stopped,
+}
+
+impl BackendState {
+    fn new() -> Self {
+        Self(Mutex::new(Backend {
+            child: None,
+            port: DEFAULT_BACKEND_PORT,
+            status: BackendStatus::Stopped,
+            external: false,
+        }))
+    }
+
+    fn port(&self) -> u16 {
+        self.0.lock().unwrap().port
+    }
+
+    fn set_status(&self, status: BackendStatus) {
+        log::debug!("Backend status: {:?}", status);
+        self.0.lock().unwrap().status = status;
+    }
+
+    /// Take the process handle, e.g. to kill it, leaving None behind
+    fn take_child(&self) -> Option<CommandChild> {
+        self.0.lock().unwrap().child.take()
+    }
+}
+
+/// State set once the app starts shutting down, so the backend exiting isn't
+/// mistaken for a crash
+struct ShuttingDown(AtomicBool);
+
+/// State set when the user asks for a backend restart, so the supervisor
+/// brings it straight back up instead of treating the exit as a crash
+struct RestartRequested(AtomicBool);
+
+/// State for moving the backend to another port when it reports that its own
+/// was taken between picking it and binding it
+#[derive(Default)]
+struct PortConflict {
+    /// Set while the first backend starts, the only time a conflict is acted on
+    watching: AtomicBool,
+    /// Set once the backend reported the conflict and was stopped
+    seen: AtomicBool,
+}
+
+/// Payload of "backend://progress", sent on every health check, of
+/// "backend://starting", sent on each one the backend isn't ready for yet,
+/// and of "backend://ready", sent once it answers
+#[derive(Clone, serde::Serialize)]
+struct BackendProgress {
+    /// Health checks made so far
+    attempt: u32,
+    /// Most health checks that fit in the startup timeout, for a progress bar
+    max: u32,
+    /// Time since the health checks started
+    elapsed_ms: u64,
+}
+
+/// Payload of "backend://crashed", sent when the backend exits unexpectedly
+#[derive(Clone, serde::Serialize)]
+struct BackendCrash {
+    /// Exit code of the process
+    code: Option<i32>,
+}
+
+/// Payload of "backend://restarting" and "backend://failed", sent as the
+/// supervisor restarts a stopped backend or gives up
+#[derive(Clone, serde::Serialize)]
+struct BackendRestart {
+    /// Restarts so far this session
+    attempt: u32,
+    /// Exit code of the process that stopped
+    code: Option<i32>,
+}
+
+/// A component of the app and the version it reports, as listed by `list_tools`
+#[derive(serde::Serialize)]
+struct ToolInfo {
+    name: String,
+    version: Option<String>,
+    /// Why the version couldn't be read, e.g. the tool isn't bundled
+    error: Option<String>,
+}
+
+impl ToolInfo {
+    fn new(name: &str, version: Result<String, String>) -> Self {
+        let (version, error) = match version {
+            Ok(version) => (Some(version), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            name: name.to_string(),
+            version,
+            error,
+        }
+    }
+}
+
+/// Result of a successful keystroke sidecar run
+#[derive(serde::Serialize)]
+struct KeystrokeOutput {
+    code: Option<i32>,
+    stdout: String,
+}
+
+/// How waiting for the backend ended
+enum Startup {
+    Ready,
+    /// The backend process exited with this code before becoming ready
+    Exited(Option<i32>),
+    TimedOut,
+}
+
+/// Port requested through TRENINO_BACKEND_PORT, if any
+fn configured_port() -> Option<u16> {
+    let value = std::env::var(BACKEND_PORT_ENV).ok()?;
+    match value.trim().parse::<u16>() {
+        Ok(port) if port != 0 => Some(port),
+        _ => {
+            log::warn!(
+                "Invalid {} '{}', picking a free port instead",
+                BACKEND_PORT_ENV, value
+            );
+            None
+        }
+    }
+}
+
+/// Host set with BACKEND_HOST_ENV, or localhost. Read once, so an invalid
+/// value is only warned about once.
+fn backend_host() -> &'static str {
+    static HOST: OnceLock<String> = OnceLock::new();
+    HOST.get_or_init(|| {
+        let Ok(value) = std::env::var(BACKEND_HOST_ENV) else {
+            return DEFAULT_BACKEND_HOST.to_string();
+        };
+        let host = value.trim();
+        let valid = !host.is_empty()
+            && !host.contains(|c: char| c.is_whitespace() || "/?#@".contains(c))
+            && (!host.contains(':') || host.parse::<IpAddr>().is_ok());
+        if valid {
+            host.to_string()
+        } else {
+            log::warn!(
+                "Invalid {} '{}', using {}",
+                BACKEND_HOST_ENV, value, DEFAULT_BACKEND_HOST
+            );
+            DEFAULT_BACKEND_HOST.to_string()
+        }
+    })
+}
+
+/// Whether the backend is reached on this machine only
+fn is_local_host(host: &str) -> bool {
+    host == DEFAULT_BACKEND_HOST || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Address the sidecar listens on: loopback unless it is reached on another
+/// host, then that address if it is one, or else every interface
+fn bind_address(host: &str) -> String {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.to_string(),
+        Err(_) if is_local_host(host) => "127.0.0.1".to_string(),
+        Err(_) => "0.0.0.0".to_string(),
+    }
+}
+
+/// Duration in milliseconds read from an environment variable, or the default
+/// if it isn't set or isn't a positive number
+fn env_duration_ms(name: &str, default: Duration) -> Duration {
+    let Ok(value) = std::env::var(name) else {
+        return default;
+    };
+    match value.trim().parse::<u64>() {
+        Ok(ms) if ms != 0 => Duration::from_millis(ms),
+        _ => {
+            log::warn!("Invalid {} '{}', using {}ms", name, value, default.as_millis());
+            default
+        }
+    }
+}
+
+/// How long to wait for the backend to become ready, see STARTUP_TIMEOUT_ENV
+fn startup_timeout() -> Duration {
+    env_duration_ms(STARTUP_TIMEOUT_ENV, STARTUP_TIMEOUT)
+}
+
+/// Longest wait between health checks, see MAX_RETRY_DELAY_ENV
+fn max_retry_delay() -> Duration {
+    env_duration_ms(MAX_RETRY_DELAY_ENV, Duration::from_millis(MAX_RETRY_DELAY_MS))
+}
+
+/// Read a window dimension override, clamped to at least `min`
+fn env_window_dimension(name: &str, default: f64, min: f64) -> f64 {
+    let Ok(value) = std::env::var(name) else {
+        return default;
+    };
+    match value.trim().parse::<f64>() {
+        Ok(size) if size.is_finite() && size > 0.0 && size <= MAX_WINDOW_DIMENSION => {
+            if size < min {
+                log::warn!("{} '{}' is below the minimum, using {}", name, value, min);
             }
+            size.max(min)
+        }
+        _ => {
+            log::warn!("Invalid {} '{}', using {}", name, value, default);
+            default
         }
-        Err(_) => {
-            // Server not yet responding
-            Ok(false)
+    }
+}
+
+/// Initial size of the main window, see WINDOW_WIDTH_ENV and WINDOW_HEIGHT_ENV
+fn window_size() -> (f64, f64) {
+    (
+        env_window_dimension(WINDOW_WIDTH_ENV, WINDOW_SIZE.0, MIN_WINDOW_SIZE.0),
+        env_window_dimension(WINDOW_HEIGHT_ENV, WINDOW_SIZE.1, MIN_WINDOW_SIZE.1),
+    )
+}
+
+/// Title the main window opens with, see WINDOW_TITLE_ENV
+fn window_title() -> String {
+    std::env::var(WINDOW_TITLE_ENV)
+        .ok()
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| WINDOW_TITLE.to_string())
+}
+
+/// Find a port nothing is listening on, preferring the default one. The
+/// listener is dropped straight away, so the port is only likely to be free
+/// by the time the backend binds it.
+fn free_port() -> u16 {
+    for candidate in [DEFAULT_BACKEND_PORT, 0] {
+        if let Ok(addr) = TcpListener::bind(("127.0.0.1", candidate)).and_then(|l| l.local_addr()) {
+            return addr.port();
         }
     }
+    DEFAULT_BACKEND_PORT
 }
 
-/// Wait for the backend to become fully ready
-fn wait_for_backend(handle: &tauri::AppHandle) -> bool {
+/// Spawn the Elixir backend sidecar on the given port and store its handle in app state.
+/// Returns a channel that receives the exit code once the process terminates.
+fn spawn_backend(
+    handle: &tauri::AppHandle,
+    port: u16,
+) -> Result<mpsc::Receiver<Option<i32>>, tauri_plugin_shell::Error> {
+    let sidecar = match handle.shell().sidecar(BACKEND_SIDECAR) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            log::error!("Failed to create sidecar command: {}", e);
+            return Err(e);
+        }
+    };
+
+    log::info!("Starting backend on port {}", port);
+    let version = handle.package_info().version.to_string();
+    let (rx, child) = match sidecar.envs(backend_env(port, &version)).spawn() {
+        Ok(result) => result,
+        Err(e) => {
+            log::error!("Failed to spawn backend sidecar: {}", e);
+            return Err(e);
+        }
+    };
+
+    // Store the child process handle in app state for cleanup on exit
+    let state = handle.state::<BackendState>();
+    let mut backend = state.0.lock().unwrap();
+    backend.child = Some(child);
+    backend.port = port;
+    backend.status = BackendStatus::Starting;
+    drop(backend);
+
+    Ok(watch_backend(handle.clone(), rx))
+}
+
+/// Where a sidecar is expected: Tauri bundles it next to the main executable
+fn sidecar_path(name: &str) -> Option<std::path::PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+}
+
+/// The expected path of the sidecar if it isn't there, e.g. in a broken build
+/// or partial install
+fn missing_sidecar(name: &str) -> Option<std::path::PathBuf> {
+    sidecar_path(name).filter(|path| !path.exists())
+}
+
+/// Environment the backend sidecar is started with
+fn backend_env(port: u16, version: &str) -> Vec<(&'static str, String)> {
+    // Resolve the directory containing this executable. The Elixir backend
+    // uses APP_PATH to locate bundled sidecars (avrdude, keystroke) that
+    // Tauri places next to the main executable in the installation directory.
+    let app_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+
+    let mut env = vec![
+        ("PORT", port.to_string()),
+        // Host in the URLs the backend generates, and the interface it listens on
+        ("PHX_HOST", backend_host().to_string()),
+        ("BIND_IP", bind_address(backend_host())),
+        ("APP_PATH", app_dir),
+        // Which launcher started the backend, for compatibility checks and
+        // platform specific behaviour such as serial port enumeration
+        ("TRENINO_SHELL_VERSION", version.to_string()),
+        ("TRENINO_OS", std::env::consts::OS.to_string()),
+        ("TRENINO_ARCH", std::env::consts::ARCH.to_string()),
+    ];
+    // Outside a Burrito release the endpoint only serves with PHX_SERVER set
+    if dev_backend() {
+        env.push(("MIX_ENV", "dev".to_string()));
+        env.push(("PHX_SERVER", "true".to_string()));
+    } else {
+        env.push(("MIX_ENV", "prod".to_string()));
+        env.push(("BURRITO", "1".to_string()));
+    }
+    // Inherited by every keystroke process the backend starts
+    if env_flag(NO_MOUSE_ENV) == Some(true) {
+        env.push(("KEYSTROKE_NO_MOUSE", "1".to_string()));
+    }
+    env
+}
+
+/// Drain the sidecar's events into the backend log, and report when it exits
+fn watch_backend(
+    handle: tauri::AppHandle,
+    mut rx: Receiver<CommandEvent>,
+) -> mpsc::Receiver<Option<i32>> {
+    let (exit_tx, exit_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let log = handle.state::<BackendLog>();
+        while let Some(event) = rx.blocking_recv() {
+            match event {
+                CommandEvent::Stdout(line) => record_output(&handle, &log, Stream::Stdout, &line),
+                CommandEvent::Stderr(line) => record_output(&handle, &log, Stream::Stderr, &line),
+                CommandEvent::Terminated(payload) => {
+                    // Nothing left to kill on shutdown
+                    let state = handle.state::<BackendState>();
+                    let mut backend = state.0.lock().unwrap();
+                    backend.child = None;
+                    backend.status = BackendStatus::Stopped;
+                    drop(backend);
+                    let expected = handle.state::<ShuttingDown>().0.load(Ordering::SeqCst)
+                        || handle.state::<RestartRequested>().0.load(Ordering::SeqCst)
+                        || handle.state::<PortConflict>().seen.load(Ordering::SeqCst);
+                    if !expected {
+                        let _ = handle.emit("backend://crashed", BackendCrash { code: payload.code });
+                    }
+                    let _ = exit_tx.send(payload.code);
+                }
+                _ => {}
+            }
+        }
+    });
+    exit_rx
+}
+
+/// Log a line of backend output, and act on it if it reports a port conflict
+fn record_output(handle: &tauri::AppHandle, log: &BackendLog, stream: Stream, line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    log.record(stream, line.trim_end());
+    if is_port_conflict(&line) {
+        stop_on_port_conflict(handle);
+    }
+}
+
+/// Whether a line of backend output reports that the port it listens on is
+/// taken, as `:eaddrinuse` from the endpoint or the OS's own message
+fn is_port_conflict(line: &str) -> bool {
+    let line = line.to_lowercase();
+    line.contains("eaddrinuse") || line.contains("address already in use")
+}
+
+/// Kill a starting backend that reported a port conflict, rather than wait
+/// for it to give up, so start_backend can move it to another port
+fn stop_on_port_conflict(handle: &tauri::AppHandle) {
+    let conflict = handle.state::<PortConflict>();
+    if !conflict.watching.load(Ordering::SeqCst) || conflict.seen.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let state = handle.state::<BackendState>();
+    log::warn!("Backend reports port {} is already in use, stopping it", state.port());
+    if let Some(child) = state.take_child() {
+        if let Err(e) = child.kill() {
+            log::warn!("Failed to stop the backend: {}", e);
+        }
+    }
+}
+
+/// Body of the backend's health endpoint
+#[derive(serde::Deserialize)]
+struct Health {
+    /// "ok" once the database is reachable and migrations have run
+    status: String,
+    /// Version of the backend, sent once it is ready
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Where the backend on the port is reached
+fn backend_url(port: u16) -> String {
+    format!("http://{}:{}", url_host(), port)
+}
+
+/// The backend host as written in a URL, with IPv6 addresses in brackets
+fn url_host() -> String {
+    match backend_host().parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+        _ => backend_host().to_string(),
+    }
+}
+
+/// HTTP client for health checks, reused across attempts
+fn health_client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// How the health endpoint is asked whether the backend is ready
+struct HealthCheck {
+    method: reqwest::Method,
+    /// The one status that counts as ready, or None for any 2xx
+    status: Option<u16>,
+}
+
+impl HealthCheck {
+    /// Whether the answer means the backend is ready. A health body must
+    /// report "ok". Without HEALTH_METHOD_ENV or HEALTH_STATUS_ENV any other
+    /// body is an error, as it is likely another program holding the port;
+    /// with them it may be a proxy, and only the status counts.
+    fn is_ready(&self, status: u16, body: &str) -> Result<bool, ()> {
+        let status_ok = match self.status {
+            Some(expected) => status == expected,
+            None => (200..300).contains(&status),
+        };
+        match serde_json::from_str::<Health>(body) {
+            Ok(health) => Ok(status_ok && health.status == "ok"),
+            Err(_) if self.is_custom() => Ok(status_ok),
+            Err(_) => Err(()),
+        }
+    }
+
+    fn is_custom(&self) -> bool {
+        self.method != reqwest::Method::GET || self.status.is_some()
+    }
+}
+
+/// The health check set through HEALTH_METHOD_ENV and HEALTH_STATUS_ENV, or
+/// a GET expecting any 2xx. Read once, so invalid values are only warned about once.
+fn health_check() -> &'static HealthCheck {
+    static CHECK: OnceLock<HealthCheck> = OnceLock::new();
+    CHECK.get_or_init(|| {
+        let method = match std::env::var(HEALTH_METHOD_ENV) {
+            Err(_) => reqwest::Method::GET,
+            Ok(value) => match value.trim().to_uppercase().as_str() {
+                "GET" => reqwest::Method::GET,
+                "HEAD" => reqwest::Method::HEAD,
+                _ => {
+                    log::warn!("Invalid {} '{}', using GET", HEALTH_METHOD_ENV, value);
+                    reqwest::Method::GET
+                }
+            },
+        };
+        let status = std::env::var(HEALTH_STATUS_ENV).ok().and_then(|value| {
+            match value.trim().parse() {
+                Ok(status @ 100..=599) => Some(status),
+                _ => {
+                    log::warn!("Invalid {} '{}', expecting any 2xx", HEALTH_STATUS_ENV, value);
+                    None
+                }
+            }
+        });
+        HealthCheck { method, status }
+    })
+}
+
+/// Check if the backend is fully ready (migrations complete) by checking health endpoint.
+/// Only a success status with a body reporting "ok" counts, so a server that
+/// answers before the app has booted, or another program on the port, doesn't.
+/// See HealthCheck for how HEALTH_METHOD_ENV and HEALTH_STATUS_ENV change that.
+fn check_backend_ready(client: &reqwest::blocking::Client, base_url: &str) -> Result<bool, String> {
+    let url = format!("{}/api/health", base_url);
+    let check = health_check();
+    let response = match client.request(check.method.clone(), &url).send() {
+        Ok(response) => response,
+        // Server not yet responding
+        Err(_) => return Ok(false),
+    };
+
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    // Server is up but not ready (e.g., migrations running) while it
+    // answers 503 with status "unavailable"
+    check
+        .is_ready(status.as_u16(), &body)
+        // Not a health body, e.g. another program is holding the port
+        .map_err(|()| format!("Unexpected response from {} (HTTP {})", url, status))
+}
+
+/// How often and for how long the health endpoint is polled during startup
+struct RetryPolicy {
+    /// Give up once this much time has passed
+    timeout: Duration,
+    /// Wait before the second check, doubling after each one
+    initial_delay: Duration,
+    /// Longest wait between checks
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The default policy, adjusted through STARTUP_TIMEOUT_ENV and MAX_RETRY_DELAY_ENV
+    fn from_env() -> Self {
+        let max_delay = max_retry_delay();
+        Self {
+            timeout: startup_timeout(),
+            initial_delay: Duration::from_millis(INITIAL_RETRY_DELAY_MS).min(max_delay),
+            max_delay,
+        }
+    }
+
+    /// How many checks are made before the timeout if each one is instant
+    fn max_attempts(&self) -> u32 {
+        let mut attempts = 1;
+        let mut waited = Duration::ZERO;
+        let mut delay = self.initial_delay;
+        while !delay.is_zero() {
+            waited += delay;
+            if waited >= self.timeout {
+                break;
+            }
+            attempts += 1;
+            delay = (delay * 2).min(self.max_delay);
+        }
+        attempts
+    }
+}
+
+/// Wait for the backend to become fully ready, or to exit
+fn wait_for_backend(
+    handle: &tauri::AppHandle,
+    port: u16,
+    exited: &mpsc::Receiver<Option<i32>>,
+) -> Startup {
     // Get the splash window to update status
     let splash_window = handle.get_webview_window("splash");
+    let policy = RetryPolicy::from_env();
+    log::info!(
+        "Waiting up to {:?} for the backend, checking at most every {:?}",
+        policy.timeout, policy.max_delay
+    );
+
+    poll_until_ready(&backend_url(port), &policy, exited, |progress, ready| {
+        let _ = handle.emit("backend://progress", progress.clone());
+        if let Some(ref window) = splash_window {
+            let _ = window.eval(format!(
+                "window.setProgress && setProgress({}, {})",
+                progress.attempt, progress.max
+            ));
+        }
+        if ready {
+            handle.state::<BackendState>().set_status(BackendStatus::Running);
+            let _ = handle.emit("backend://ready", progress);
+            return;
+        }
+        let _ = handle.emit("backend://starting", progress.clone());
+        // Update splash screen status
+        if let Some(ref window) = splash_window {
+            let elapsed = progress.elapsed_ms / 1000;
+            let phase = if elapsed < 5 {
+                "Starting backend..."
+            } else if elapsed < 15 {
+                "Running database migrations..."
+            } else {
+                "Almost ready..."
+            };
+            let _ = window.eval(format!(
+                "document.getElementById('status').textContent = '{} attempt {}'",
+                phase, progress.attempt
+            ));
+        }
+    })
+}
+
+/// Poll the health endpoint of the backend at `base_url` with backoff until
+/// it is ready, the backend exits or the policy's timeout passes. `on_check`
+/// gets the progress of each check and whether the backend was ready, except
+/// for checks answered by something other than the backend.
+fn poll_until_ready(
+    base_url: &str,
+    policy: &RetryPolicy,
+    exited: &mpsc::Receiver<Option<i32>>,
+    mut on_check: impl FnMut(BackendProgress, bool),
+) -> Startup {
+    let client = health_client();
+    let mut delay = policy.initial_delay;
+    let mut attempt = 1;
+    let max = policy.max_attempts();
 
-    for attempt in 1..=MAX_RETRIES {
-        match check_backend_ready() {
+    let started = Instant::now();
+    while started.elapsed() < policy.timeout {
+        if let Ok(code) = exited.try_recv() {
+            return Startup::Exited(code);
+        }
+
+        let progress = BackendProgress {
+            attempt,
+            max,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+        match check_backend_ready(&client, base_url) {
             Ok(true) => {
-                println!("Backend ready after {} attempts", attempt);
-                return true;
-            }
-            Ok(false) => {
-                // Update splash screen status
-                if let Some(ref window) = splash_window {
-                    let status = if attempt < 10 {
-                        "Starting server..."
-                    } else if attempt < 30 {
-                        "Running database migrations..."
-                    } else {
-                        "Almost ready..."
-                    };
-                    let _ = window.eval(&format!(
-                        "document.getElementById('status').textContent = '{}'",
-                        status
-                    ));
-                }
+                on_check(progress, true);
+                log::info!(
+                    "Backend ready after {} attempts ({:.1?})",
+                    attempt,
+                    started.elapsed()
+                );
+                return Startup::Ready;
             }
+            Ok(false) => on_check(progress, false),
             Err(e) => {
-                eprintln!("Health check error: {}", e);
+                log::warn!("Health check error: {}", e);
+            }
+        }
+
+        log::debug!(
+            "Waiting for backend... attempt {} ({:.1?} elapsed)",
+            attempt,
+            started.elapsed()
+        );
+        std::thread::sleep(jittered(delay));
+        delay = (delay * 2).min(policy.max_delay);
+        attempt += 1;
+    }
+    Startup::TimedOut
+}
+
+/// `delay` lengthened by a random fraction of up to RETRY_JITTER. Only ever
+/// longer, so no more checks are made than RetryPolicy::max_attempts counts.
+fn jittered(delay: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+    // Seeded randomly for each RandomState, which is plenty for spreading polls
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let fraction = (random % 1000) as f64 / 1000.0;
+    delay + delay.mul_f64(fraction * RETRY_JITTER)
+}
+
+/// Read an on/off environment variable. Any value other than 0, false, no or
+/// off turns it on.
+fn env_flag(name: &str) -> Option<bool> {
+    let value = std::env::var(name).ok()?;
+    Some(!matches!(
+        value.trim().to_lowercase().as_str(),
+        "0" | "false" | "no" | "off"
+    ))
+}
+
+/// Describe an exit code for the user
+fn exit_description(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("with code {}", code),
+        None => "without an exit code".to_string(),
+    }
+}
+
+/// Spawn the backend and wait for it to become ready. Uses the configured
+/// port as is; otherwise picks a free one and moves to another if the backend
+/// exits or reports that it was taken meanwhile. Returns the port and exit channel of
+/// the running backend, or what went wrong for the error dialog.
+fn start_backend(
+    handle: &tauri::AppHandle,
+    configured: Option<u16>,
+) -> Result<(u16, mpsc::Receiver<Option<i32>>), String> {
+    if let Some(path) = missing_sidecar(BACKEND_SIDECAR) {
+        log::error!("Backend executable not found at {}", path.display());
+        return Err(format!(
+            "The Trenino backend executable could not be found. It is expected at:\n{}\n\n\
+             The installation looks incomplete, reinstalling Trenino should fix this.",
+            path.display()
+        ));
+    }
+
+    let conflict = handle.state::<PortConflict>();
+    conflict.watching.store(true, Ordering::SeqCst);
+    let result = spawn_until_ready(handle, configured, &conflict);
+    conflict.watching.store(false, Ordering::SeqCst);
+    result
+}
+
+/// The attempts of start_backend, made while port conflicts are watched for
+fn spawn_until_ready(
+    handle: &tauri::AppHandle,
+    configured: Option<u16>,
+    conflict: &PortConflict,
+) -> Result<(u16, mpsc::Receiver<Option<i32>>), String> {
+    let mut port = configured.unwrap_or_else(free_port);
+    for spawn_attempt in 1..=MAX_SPAWN_ATTEMPTS {
+        conflict.seen.store(false, Ordering::SeqCst);
+        // Spawn errors, e.g. a missing sidecar binary, won't go away on retry
+        let exited = spawn_backend(handle, port)
+            .map_err(|e| format!("The Trenino backend could not be started: {}", e))?;
+
+        let startup = wait_for_backend(handle, port, &exited);
+        let port_taken = conflict.seen.load(Ordering::SeqCst);
+        match startup {
+            Startup::Ready => {
+                log::info!("Backend running on port {}", port);
+                return Ok((port, exited));
+            }
+            Startup::Exited(_) if port_taken && configured.is_some() => {
+                log::error!("Configured port {} is already in use", port);
+                return Err(format!(
+                    "The Trenino backend could not listen on port {}, another program is using it. \
+                     Close it or pick another port with {}.",
+                    port, BACKEND_PORT_ENV
+                ));
+            }
+            Startup::Exited(code) if configured.is_none() && spawn_attempt < MAX_SPAWN_ATTEMPTS => {
+                let taken = port;
+                port = free_port();
+                if port_taken {
+                    log::warn!(
+                        "Port {} was taken, retrying on port {} (attempt {}/{})",
+                        taken, port, spawn_attempt + 1, MAX_SPAWN_ATTEMPTS
+                    );
+                } else {
+                    log::warn!(
+                        "Backend exited during startup (code {:?}), retrying on port {} (attempt {}/{})",
+                        code, port, spawn_attempt + 1, MAX_SPAWN_ATTEMPTS
+                    );
+                }
+            }
+            Startup::Exited(code) => {
+                log::error!("Backend exited during startup (code {:?})", code);
+                return Err(format!(
+                    "The Trenino backend exited during startup {}.",
+                    exit_description(code)
+                ));
+            }
+            Startup::TimedOut => {
+                let timeout = startup_timeout();
+                log::error!("Backend did not become ready within {:?}", timeout);
+                return Err(format!(
+                    "The Trenino backend is running but did not become ready within {} seconds.",
+                    timeout.as_secs()
+                ));
             }
         }
+    }
+    unreachable!("the last spawn attempt always returns")
+}
+
+/// Wait for a backend started outside Trenino to become ready on the port
+fn attach_backend(
+    handle: &tauri::AppHandle,
+    port: u16,
+) -> Result<(u16, mpsc::Receiver<Option<i32>>), String> {
+    log::info!("Using the backend running separately on port {}", port);
+    let state = handle.state::<BackendState>();
+    let mut backend = state.0.lock().unwrap();
+    backend.port = port;
+    backend.external = true;
+    drop(backend);
 
-        println!("Waiting for backend... attempt {}/{}", attempt, MAX_RETRIES);
-        std::thread::sleep(Duration::from_millis(RETRY_DELAY_MS));
+    // Never reports an exit, there is no process to watch
+    let (_, exited) = mpsc::channel();
+    match wait_for_backend(handle, port, &exited) {
+        Startup::Ready => Ok((port, exited)),
+        _ => Err(format!("Could not reach the Trenino backend on port {}.", port)),
     }
-    false
+}
+
+/// Whether a crashed backend should be restarted, see RESTART_ENV
+fn restart_enabled() -> bool {
+    env_flag(RESTART_ENV).unwrap_or(true)
+}
+
+/// Whether to debug the launcher, see DEV_ENV
+fn dev_mode() -> bool {
+    env_flag(DEV_ENV) == Some(true)
+}
+
+/// Whether the backend is a dev build rather than a release, see BACKEND_ENV_ENV
+fn dev_backend() -> bool {
+    match std::env::var(BACKEND_ENV_ENV).as_deref().map(str::trim) {
+        Err(_) | Ok("") | Ok("prod") => false,
+        Ok("dev") => true,
+        Ok(value) => {
+            log::warn!("Invalid {} '{}', using prod", BACKEND_ENV_ENV, value);
+            false
+        }
+    }
+}
+
+/// Whether to run without any window, see HEADLESS_ENV
+fn headless_mode() -> bool {
+    env_flag(HEADLESS_ENV) == Some(true)
+}
+
+/// Whether to only log what the launcher would do, see SIMULATE_ENV
+fn simulate_mode() -> bool {
+    env_flag(SIMULATE_ENV) == Some(true)
+}
+
+/// Print the decisions startup would make and pretend the backend became
+/// ready. Runs before Tauri is set up, since that already needs a display,
+/// so it prints to stdout instead of going through the log plugin.
+fn simulate() {
+    let configured = configured_port();
+    let port = configured.unwrap_or_else(free_port);
+    println!("Simulating startup, {} is set", SIMULATE_ENV);
+
+    if env_flag(EXTERNAL_BACKEND_ENV) == Some(true) {
+        println!("Would use the backend running separately on port {}", port);
+    } else {
+        match configured {
+            Some(port) => println!("Would start the backend on the configured port {}", port),
+            None => println!("Would start the backend on free port {}", port),
+        }
+        match sidecar_path(BACKEND_SIDECAR) {
+            Some(path) if path.exists() => println!("Sidecar: {}", path.display()),
+            Some(path) => println!("Sidecar: {} (missing)", path.display()),
+            None => println!("Sidecar: {}", BACKEND_SIDECAR),
+        }
+        println!("Environment:");
+        for (name, value) in backend_env(port, env!("CARGO_PKG_VERSION")) {
+            println!("  {}={}", name, value);
+        }
+        println!("Restart on crash: {}", restart_enabled());
+    }
+
+    let check = health_check();
+    println!(
+        "Would wait up to {:?} for {} {}/api/health to answer {}, checking at most every {:?}",
+        startup_timeout(),
+        check.method,
+        backend_url(port),
+        check.status.map_or("2xx".to_string(), |status| status.to_string()),
+        max_retry_delay()
+    );
+    if headless_mode() {
+        println!("Would run headless, without opening a window");
+    } else {
+        let (width, height) = window_size();
+        println!("Would open '{}' at {}x{}", window_title(), width, height);
+    }
+    println!("Backend ready (simulated), exiting");
+}
+
+/// Version the backend on the port reports through its health endpoint
+fn backend_version(port: u16) -> Result<String, String> {
+    let url = format!("{}/api/health", backend_url(port));
+    let body = health_client()
+        .get(&url)
+        .send()
+        .and_then(|response| response.text())
+        .map_err(|e| format!("Could not ask the backend: {}", e))?;
+    let health: Health = serde_json::from_str(&body)
+        .map_err(|e| format!("Unexpected health response: {}", e))?;
+    health
+        .version
+        .ok_or_else(|| format!("The backend isn't ready ({})", health.status))
+}
+
+/// The line of a tool's output that names its version, or else the first one
+fn version_line(output: &str) -> Option<String> {
+    let lines = || output.lines().map(str::trim).filter(|line| !line.is_empty());
+    lines()
+        .find(|line| line.to_lowercase().contains("version"))
+        .or_else(|| lines().next())
+        .map(str::to_string)
+}
+
+/// Whether to use a backend that is already running on the port instead of
+/// spawning the sidecar, e.g. one started with `mix phx.server` during development
+fn use_external_backend(port: u16) -> bool {
+    if env_flag(EXTERNAL_BACKEND_ENV) == Some(true) {
+        return true;
+    }
+    matches!(check_backend_ready(&health_client(), &backend_url(port)), Ok(true))
+}
+
+/// Watch the running backend and restart it with backoff if it exits while
+/// the app is still open. Returns once it is down for good.
+fn supervise_backend(handle: &tauri::AppHandle, mut exited: mpsc::Receiver<Option<i32>>) {
+    let mut delay = Duration::from_millis(INITIAL_RESTART_DELAY_MS);
+    let mut restarts = 0;
+
+    // recv only fails if the process watcher is gone, i.e. nothing left to supervise
+    let Ok(mut code) = exited.recv() else {
+        return;
+    };
+
+    loop {
+        if handle.state::<ShuttingDown>().0.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if handle.state::<RestartRequested>().0.swap(false, Ordering::SeqCst) {
+            // Requested restarts don't count towards the crash limit
+            log::info!("Restarting backend on request");
+            handle.state::<BackendState>().set_status(BackendStatus::Restarting);
+            let _ = handle.emit("backend://restarting", BackendRestart { attempt: restarts, code });
+        } else {
+            log::error!("Backend exited unexpectedly (code {:?})", code);
+
+            if !restart_enabled() || restarts == MAX_RESTARTS {
+                let _ = handle.emit("backend://failed", BackendRestart { attempt: restarts, code });
+                return;
+            }
+
+            restarts += 1;
+            handle.state::<BackendState>().set_status(BackendStatus::Restarting);
+            let _ = handle.emit("backend://restarting", BackendRestart { attempt: restarts, code });
+            log::info!(
+                "Restarting backend in {:?} (restart {}/{})",
+                delay, restarts, MAX_RESTARTS
+            );
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(Duration::from_millis(MAX_RESTART_DELAY_MS));
+        }
+
+        let port = handle.state::<BackendState>().port();
+        exited = match spawn_backend(handle, port) {
+            Ok(rx) => rx,
+            Err(_) => {
+                handle.state::<BackendState>().set_status(BackendStatus::Stopped);
+                let _ = handle.emit("backend://failed", BackendRestart { attempt: restarts, code });
+                return;
+            }
+        };
+
+        code = match wait_for_backend(handle, port, &exited) {
+            Startup::Ready => {
+                match exited.recv() {
+                    Ok(code) => code,
+                    Err(_) => return,
+                }
+            }
+            Startup::Exited(code) => code,
+            Startup::TimedOut => {
+                log::error!("Restarted backend did not become ready");
+                let _ = handle.emit("backend://failed", BackendRestart { attempt: restarts, code });
+                return;
+            }
+        };
+    }
+}
+
+/// Supervise the backend and, unless the app is quitting, report that it is
+/// down for good and exit
+fn supervise_until_stopped(handle: &tauri::AppHandle, exited: mpsc::Receiver<Option<i32>>) {
+    supervise_backend(handle, exited);
+    if !handle.state::<ShuttingDown>().0.load(Ordering::SeqCst) {
+        report_backend_error(handle, "The Trenino backend stopped and could not be restarted.");
+        shutdown_backend(handle);
+        std::process::exit(1);
+    }
+}
+
+/// Stop the running backend so the supervisor starts it again on the same port.
+/// Only possible once startup has finished and the supervisor is watching it.
+fn request_restart(handle: &tauri::AppHandle) -> Result<(), String> {
+    let state = handle.state::<BackendState>();
+    let mut backend = state.0.lock().unwrap();
+    if backend.external {
+        return Err("The backend was started outside Trenino".to_string());
+    }
+    if handle.get_webview_window("main").is_none() {
+        return Err("The backend is still starting".to_string());
+    }
+    if backend.status == BackendStatus::Restarting {
+        return Err("The backend is already restarting".to_string());
+    }
+    let Some(child) = backend.child.take() else {
+        return Err("The backend is not running".to_string());
+    };
+    drop(backend);
+
+    handle.state::<RestartRequested>().0.store(true, Ordering::SeqCst);
+    if let Err(e) = child.kill() {
+        handle.state::<RestartRequested>().0.store(false, Ordering::SeqCst);
+        return Err(format!("Failed to stop the backend: {}", e));
+    }
+    Ok(())
+}
+
+/// The most recent lines of backend output, oldest first, e.g. to attach to a
+/// bug report. TRENINO_LOG_BUFFER_LINES sets how many are kept.
+#[tauri::command]
+fn backend_output(app: tauri::AppHandle) -> Vec<String> {
+    app.state::<BackendLog>().recent_lines()
+}
+
+/// The serial ports a device may be connected to, with how each is connected
+/// and its USB ids. Bluetooth, built-in and virtual ports are left out unless
+/// `all` is set, since opening them to probe can have side effects.
+#[tauri::command]
+fn list_serial_ports(all: Option<bool>) -> Result<Vec<serial_ports::SerialPort>, String> {
+    serial_ports::list(all.unwrap_or(false))
+}
+
+/// Restart the backend from the frontend, e.g. when it stops responding.
+/// Returns once the old process is stopped; "backend://restarting" and then
+/// "backend://ready" or "backend://failed" report how the restart goes.
+#[tauri::command]
+fn restart_backend(app: tauri::AppHandle) -> Result<(), String> {
+    request_restart(&app)
+}
+
+/// Set the main window's title, e.g. to the active train profile. An empty
+/// title goes back to the one the window opened with.
+#[tauri::command]
+fn set_window_title(app: tauri::AppHandle, title: String) -> Result<(), String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "The main window isn't open".to_string())?;
+    let title = match title.trim() {
+        "" => window_title(),
+        title => title.to_string(),
+    };
+    window.set_title(&title).map_err(|e| e.to_string())
+}
+
+/// The launcher, the backend and every bundled tool with the version each
+/// reports, for an about or diagnostics panel. A tool that can't be asked is
+/// listed with the error instead.
+#[tauri::command]
+async fn list_tools(app: tauri::AppHandle) -> Vec<ToolInfo> {
+    let mut tools = vec![ToolInfo::new(
+        "trenino",
+        Ok(app.package_info().version.to_string()),
+    )];
+
+    let port = app.state::<BackendState>().port();
+    let backend = tauri::async_runtime::spawn_blocking(move || backend_version(port))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    tools.push(ToolInfo::new(BACKEND_SIDECAR, backend));
+
+    for (name, args) in VERSIONED_SIDECARS {
+        let version = match missing_sidecar(name) {
+            Some(path) => Err(format!("Not bundled, expected at {}", path.display())),
+            None => sidecar_version(&app, name, args).await,
+        };
+        tools.push(ToolInfo::new(name, version));
+    }
+    tools
+}
+
+/// Run a sidecar with `args` and read the version it prints. Tools that only
+/// print it with their usage may exit with an error, so the status is ignored.
+async fn sidecar_version(
+    app: &tauri::AppHandle,
+    name: &str,
+    args: &[&str],
+) -> Result<String, String> {
+    let output = app
+        .shell()
+        .sidecar(name)
+        .map_err(|e| e.to_string())?
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", name, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    version_line(&stdout)
+        .or_else(|| version_line(&stderr))
+        .ok_or_else(|| format!("{} printed no version", name))
+}
+
+/// Arguments of a keystroke run for `send_keystroke`. Bindings name keys in
+/// uppercase ("W", "CTRL+S"), so they must not be read as Shift+letter. The
+/// key comes after "--", so one starting with "-" isn't taken for a flag.
+fn keystroke_args<'a>(action: &'a str, key: &'a str) -> [&'a str; 4] {
+    [action, "--no-auto-shift", "--", key]
+}
+
+/// Press, release or tap a key with the bundled keystroke tool, e.g. `"tap"`
+/// and `"CTRL+S"`. Fails with the tool's error message, see `keystroke_error`.
+#[tauri::command]
+async fn send_keystroke(
+    app: tauri::AppHandle,
+    action: String,
+    key: String,
+) -> Result<KeystrokeOutput, String> {
+    if !matches!(action.as_str(), "down" | "up" | "tap") {
+        return Err(format!("Unknown action '{}', expected down, up or tap", action));
+    }
+
+    let output = app
+        .shell()
+        .sidecar("keystroke")
+        .map_err(|e| format!("Failed to create keystroke command: {}", e))?
+        .args(keystroke_args(&action, &key))
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run keystroke: {}", e))?;
+
+    let code = output.status.code();
+    if code != Some(0) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(keystroke_error(code, &key, stderr.trim()));
+    }
+    Ok(KeystrokeOutput {
+        code,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+    })
+}
+
+/// The error for a failed keystroke run, by its exit status: 10 for an
+/// invalid key, 20 for input the OS rejected, 30 for a missing input
+/// permission and 40 for a target window that doesn't exist
+fn keystroke_error(code: Option<i32>, key: &str, stderr: &str) -> String {
+    match code {
+        Some(10) => format!("Invalid keystroke '{}': {}", key, stderr),
+        Some(20) => format!("Could not send keystroke '{}': {}", key, stderr),
+        Some(30) => format!("Not allowed to send keystroke '{}': {}", key, stderr),
+        Some(40) => format!("No window to send keystroke '{}' to: {}", key, stderr),
+        _ => format!("keystroke exited with code {:?}: {}", code, stderr),
+    }
+}
+
+/// Report that the backend isn't running: in a dialog, or only in the log
+/// when headless, where nobody may be around to dismiss one
+fn report_backend_error(handle: &tauri::AppHandle, headline: &str) {
+    if headless_mode() {
+        log::error!("{}", headline);
+    } else {
+        show_backend_error(handle, headline);
+    }
+}
+
+/// Tell the user the backend isn't running, showing its last output and
+/// offering to open the log file. Blocks until the dialog is dismissed.
+fn show_backend_error(handle: &tauri::AppHandle, headline: &str) {
+    let log = handle.state::<BackendLog>();
+    let recent = log.recent_lines();
+    let tail = &recent[recent.len().saturating_sub(DIALOG_LOG_LINES)..];
+
+    let mut message = headline.to_string();
+    if !tail.is_empty() {
+        message.push_str("\n\nLast output:\n");
+        message.push_str(&tail.join("\n"));
+    }
+    if let Some(path) = log.path() {
+        message.push_str(&format!("\n\nFull log: {}", path.display()));
+    }
+
+    let open_logs = handle
+        .dialog()
+        .message(message)
+        .title("Trenino backend error")
+        .kind(MessageDialogKind::Error)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Open logs".to_string(),
+            "Quit".to_string(),
+        ))
+        .blocking_show();
+
+    if open_logs {
+        if let Some(path) = log.path() {
+            if let Err(e) = handle.opener().reveal_item_in_dir(path) {
+                log::warn!("Could not open the log directory: {}", e);
+            }
+        }
+    }
+}
+
+/// Stop the backend before the app exits so it doesn't keep holding its port.
+/// Asks it to shut down gracefully, then kills it if it is still running after
+/// the grace period. Only the first call does anything.
+fn shutdown_backend(handle: &tauri::AppHandle) {
+    if handle.state::<ShuttingDown>().0.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let Some(backend_state) = handle.try_state::<BackendState>() else {
+        return;
+    };
+    if backend_state.0.lock().unwrap().child.is_none() {
+        return;
+    }
+
+    // Gracefully shut down the backend by calling the shutdown endpoint
+    log::info!("App exit requested, initiating graceful backend shutdown");
+    let port = backend_state.port();
+    let shutdown_url = format!("{}/api/shutdown", backend_url(port));
+    let mut shutdown_succeeded = false;
+
+    // Send POST request to shutdown endpoint
+    // This tells the Elixir backend to call System.stop(0) which
+    // gracefully shuts down the Erlang VM and all child processes
+    match reqwest::blocking::Client::new()
+        .post(&shutdown_url)
+        .timeout(Duration::from_secs(2))
+        .send()
+    {
+        Ok(response) => {
+            if response.status().is_success() {
+                log::info!("Backend shutdown initiated successfully");
+                shutdown_succeeded = true;
+            } else {
+                log::warn!("Backend shutdown returned status: {}", response.status());
+            }
+        }
+        Err(e) => {
+            // Backend might already be down or unreachable
+            log::warn!("Could not reach backend for shutdown: {}", e);
+        }
+    }
+
+    // If graceful shutdown succeeded, give it time to complete. The process
+    // watcher clears the handle once the backend has exited.
+    if shutdown_succeeded {
+        let started = Instant::now();
+        while started.elapsed() < SHUTDOWN_GRACE_PERIOD {
+            if backend_state.0.lock().unwrap().child.is_none() {
+                log::info!("Backend exited after {:.1?}", started.elapsed());
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    // Otherwise, fall back to killing the process directly
+    if let Some(child) = backend_state.take_child() {
+        log::warn!("Falling back to forceful process termination");
+        if let Err(e) = child.kill() {
+            log::error!("Failed to kill backend: {}", e);
+        }
+    }
+}
+
+/// Bring the running instance to the front when Trenino is launched again.
+/// The splash screen stands in for the main window while the backend starts.
+#[cfg(desktop)]
+fn focus_existing_window(handle: &tauri::AppHandle) {
+    let window = handle
+        .get_webview_window("main")
+        .or_else(|| handle.get_webview_window("splash"));
+    if let Some(window) = window {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Output of `keystroke check-permissions --format json`
+#[cfg(target_os = "macos")]
+#[derive(serde::Deserialize)]
+struct PermissionReport {
+    ok: bool,
+    permissions: Vec<Permission>,
+}
+
+#[cfg(target_os = "macos")]
+#[derive(serde::Deserialize)]
+struct Permission {
+    name: String,
+    granted: bool,
+    required: bool,
+    settings_url: String,
+}
+
+/// Warn if macOS won't let keystrokes through yet, offering to open the
+/// settings to allow them. Blocks until the dialog is dismissed.
+#[cfg(target_os = "macos")]
+fn check_input_permissions(handle: &tauri::AppHandle) {
+    let command = match handle.shell().sidecar("keystroke") {
+        Ok(command) => command.args(["check-permissions", "--format", "json"]),
+        Err(e) => {
+            log::warn!("Could not check the input permissions: {}", e);
+            return;
+        }
+    };
+    // Missing permissions exit with an error status, the report is on stdout either way
+    let report = match tauri::async_runtime::block_on(command.output()) {
+        Ok(output) => serde_json::from_slice::<PermissionReport>(&output.stdout),
+        Err(e) => {
+            log::warn!("Could not check the input permissions: {}", e);
+            return;
+        }
+    };
+    let report = match report {
+        Ok(report) if report.ok => return,
+        Ok(report) => report,
+        Err(e) => {
+            log::warn!("Unexpected output from keystroke check-permissions: {}", e);
+            return;
+        }
+    };
+
+    let missing: Vec<_> = report
+        .permissions
+        .into_iter()
+        .filter(|p| p.required && !p.granted)
+        .collect();
+    let names: Vec<_> = missing.iter().map(|p| p.name.as_str()).collect();
+    log::warn!("Missing input permissions: {}", names.join(", "));
+
+    let open = handle
+        .dialog()
+        .message(format!(
+            "Trenino can't send keystrokes to the sim until it is allowed under \
+             System Settings > Privacy & Security > {}.\n\n\
+             Enable Trenino there, then restart it.",
+            names.join(" and ")
+        ))
+        .title("Trenino needs permission")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Open System Settings".to_string(),
+            "Later".to_string(),
+        ))
+        .blocking_show();
+
+    if open {
+        for permission in &missing {
+            if let Err(e) = handle.opener().open_url(&permission.settings_url, None::<&str>) {
+                log::warn!("Could not open the {} settings: {}", permission.name, e);
+            }
+        }
+    }
+}
+
+/// Show an error that isn't about the backend. Blocks until it is dismissed.
+fn show_error(handle: &tauri::AppHandle, message: &str) {
+    handle
+        .dialog()
+        .message(message)
+        .title("Trenino error")
+        .kind(MessageDialogKind::Error)
+        .blocking_show();
+}
+
+/// Create the splash screen shown while the backend starts
+fn create_splash_window(handle: &tauri::AppHandle) -> Result<WebviewWindow, String> {
+    let splash_html = include_str!("../splash.html");
+    let splash_url = format!("data:text/html,{}", urlencoding::encode(splash_html));
+    let url = splash_url.parse().map_err(|e| format!("Invalid splash URL: {}", e))?;
+
+    WebviewWindowBuilder::new(handle, "splash", WebviewUrl::External(url))
+        .title("Trenino")
+        .inner_size(400.0, 300.0)
+        .resizable(false)
+        .decorations(false)
+        .center()
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Create the main window, showing the UI served by the backend on the port
+fn create_main_window(handle: &tauri::AppHandle, port: u16) -> Result<WebviewWindow, String> {
+    let url = backend_url(port);
+    let url = url.parse().map_err(|e| format!("Invalid backend URL '{}': {}", url, e))?;
+
+    // The backend capability only lets pages on localhost call into the app
+    if !is_local_host(backend_host()) {
+        handle
+            .add_capability(
+                CapabilityBuilder::new("backend-host")
+                    .window("main")
+                    .remote(format!("http://{}:*", url_host()))
+                    .permission("core:event:default"),
+            )
+            .map_err(|e| format!("Failed to allow the backend host: {}", e))?;
+    }
+
+    let (width, height) = window_size();
+    WebviewWindowBuilder::new(handle, "main", WebviewUrl::External(url))
+        .title(window_title())
+        .inner_size(width, height)
+        .min_inner_size(MIN_WINDOW_SIZE.0, MIN_WINDOW_SIZE.1)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Add the tray icon, with shortcuts to show the window, restart the backend and quit
+#[cfg(desktop)]
+fn create_tray(app: &tauri::App) -> tauri::Result<()> {
+    // Headless, there is no window to show, only the UI served by the backend
+    let show = if headless_mode() {
+        MenuItem::with_id(app, "browse", "Open in browser", true, None::<&str>)?
+    } else {
+        MenuItem::with_id(app, "show", "Show window", true, None::<&str>)?
+    };
+    let restart = MenuItem::with_id(app, "restart", "Restart backend", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&show, &restart, &quit])?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("Trenino")
+        .menu(&menu)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => focus_existing_window(app),
+            "browse" => {
+                let port = app.state::<BackendState>().port();
+                if let Err(e) = app.opener().open_url(backend_url(port), None::<&str>) {
+                    log::warn!("Could not open the browser: {}", e);
+                }
+            }
+            "restart" => {
+                if let Err(e) = request_restart(app) {
+                    log::warn!("Could not restart backend: {}", e);
+                }
+            }
+            "quit" => {
+                shutdown_backend(app);
+                app.exit(0);
+            }
+            _ => {}
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    Ok(())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    #[cfg(desktop)]
+    cli::apply();
+
+    if simulate_mode() {
+        simulate();
+        return;
+    }
+
+    let builder = tauri::Builder::default();
+
+    // A second instance would start another backend fighting over the same
+    // port, so it hands over to this one and exits. Must be the first plugin.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+        log::info!("Trenino is already running, focusing the existing window");
+        focus_existing_window(app);
+    }));
+
+    // Reopen the main window where it was last left. The plugin only restores
+    // a position that is on a connected monitor, and the window's minimum
+    // size still applies. The splash screen is always centered.
+    #[cfg(desktop)]
+    let builder = builder.plugin(
+        tauri_plugin_window_state::Builder::new()
+            .with_denylist(&["splash"])
+            .build(),
+    );
+
+    let log_level = if dev_mode() {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+
+    builder
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .level(log_level)
+                // Health checks would otherwise log every connection at debug level
+                .level_for("reqwest", log::LevelFilter::Info)
+                .level_for("hyper_util", log::LevelFilter::Info)
+                .targets([
+                    Target::new(TargetKind::Stdout),
+                    Target::new(TargetKind::LogDir {
+                        file_name: Some(LOG_FILE_NAME.to_string()),
+                    }),
+                ])
+                .build(),
+        )
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
-        .manage(BackendProcess(Mutex::new(None)))
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![
+            restart_backend,
+            send_keystroke,
+            set_window_title,
+            list_tools,
+            backend_output,
+            list_serial_ports
+        ])
+        .manage(BackendState::new())
+        .manage(ShuttingDown(AtomicBool::new(false)))
+        .manage(RestartRequested(AtomicBool::new(false)))
+        .manage(PortConflict::default())
         .setup(|app| {
             let handle = app.handle().clone();
+            app.manage(BackendLog::open(&handle));
 
-            // Create splash screen window first
-            let splash_html = include_str!("../splash.html");
-            let splash_url = format!("data:text/html,{}", urlencoding::encode(splash_html));
+            #[cfg(desktop)]
+            create_tray(app)?;
 
-            let splash_window = WebviewWindowBuilder::new(
-                &handle,
-                "splash",
-                WebviewUrl::External(splash_url.parse().unwrap()),
-            )
-            .title("Trenino")
-            .inner_size(400.0, 300.0)
-            .resizable(false)
-            .decorations(false)
-            .center()
-            .build()
-            .expect("Failed to create splash window");
-
-            // Spawn the Elixir backend as a sidecar process
-            let sidecar = match handle.shell().sidecar("trenino_backend") {
-                Ok(cmd) => cmd,
-                Err(e) => {
-                    eprintln!("Failed to create sidecar command: {}", e);
-                    return Err(Box::new(e));
-                }
-            };
+            // Stopping the launcher, e.g. a service manager stopping a
+            // headless one, stops the backend with it
+            let signal_handle = handle.clone();
+            if let Err(e) = ctrlc::set_handler(move || {
+                log::info!("Received a termination signal, shutting down");
+                shutdown_backend(&signal_handle);
+                signal_handle.exit(0);
+            }) {
+                log::warn!("Could not handle termination signals: {}", e);
+            }
 
-            // Resolve the directory containing this executable. The Elixir backend
-            // uses APP_PATH to locate bundled sidecars (avrdude, keystroke) that
-            // Tauri places next to the main executable in the installation directory.
-            let app_dir = std::env::current_exe()
-                .ok()
-                .and_then(|p| p.parent().map(|d| d.to_string_lossy().into_owned()))
-                .unwrap_or_default();
-
-            let (mut _rx, child) = match sidecar
-                .env("PORT", BACKEND_PORT.to_string())
-                .env("MIX_ENV", "prod")
-                .env("BURRITO", "1")
-                .env("APP_PATH", app_dir)
-                .spawn()
-            {
-                Ok(result) => result,
-                Err(e) => {
-                    eprintln!("Failed to spawn backend sidecar: {}", e);
-                    return Err(Box::new(e));
-                }
+            // Create splash screen window first. Startup carries on without
+            // it, the main window is what matters.
+            let headless = headless_mode();
+            let splash_window = if headless {
+                None
+            } else {
+                create_splash_window(&handle)
+                    .map_err(|e| log::warn!("Could not create the splash screen: {}", e))
+                    .ok()
             };
 
-            // Store the child process handle in app state for cleanup on exit
-            let backend_state: tauri::State<BackendProcess> = app.state();
-            *backend_state.0.lock().unwrap() = Some(child);
-
             // Wait for backend to be ready in a separate thread
             let splash_handle = splash_window;
             std::thread::spawn(move || {
-                if wait_for_backend(&handle) {
-                    // Create the main window once backend is ready
-                    let url = format!("http://localhost:{}", BACKEND_PORT);
-
-                    let main_window = WebviewWindowBuilder::new(
-                        &handle,
-                        "main",
-                        WebviewUrl::External(url.parse().unwrap()),
-                    )
-                    .title("Trenino")
-                    .inner_size(1200.0, 800.0)
-                    .min_inner_size(800.0, 600.0)
-                    .build()
-                    .expect("Failed to create main window");
-
-                    // Close splash and show main window
-                    let _ = splash_handle.close();
-                    let _ = main_window.show();
+                let configured = configured_port();
+                let external = use_external_backend(configured.unwrap_or(DEFAULT_BACKEND_PORT));
+                let started = if external {
+                    attach_backend(&handle, configured.unwrap_or(DEFAULT_BACKEND_PORT))
                 } else {
-                    eprintln!("Backend failed to start after {} attempts", MAX_RETRIES);
-                    // Show error on splash screen before exiting
-                    let _ = splash_handle.eval(
-                        "document.getElementById('status').textContent = 'Failed to start. Please restart the app.';\
-                         document.getElementById('status').style.color = '#ef4444';"
-                    );
-                    std::thread::sleep(Duration::from_secs(3));
-                    std::process::exit(1);
+                    start_backend(&handle, configured)
+                };
+
+                match started {
+                    Ok((port, exited)) if headless => {
+                        log::info!("Backend ready at {}, running headless", backend_url(port));
+                        #[cfg(target_os = "macos")]
+                        {
+                            let handle = handle.clone();
+                            std::thread::spawn(move || check_input_permissions(&handle));
+                        }
+
+                        if external {
+                            return;
+                        }
+                        supervise_until_stopped(&handle, exited);
+                    }
+                    Ok((port, exited)) => {
+                        // Create the main window once backend is ready
+                        let main_window = match create_main_window(&handle, port) {
+                            Ok(window) => window,
+                            Err(e) => {
+                                log::error!("Could not create the main window: {}", e);
+                                shutdown_backend(&handle);
+                                show_error(
+                                    &handle,
+                                    &format!("Trenino could not open its window.\n\n{}", e),
+                                );
+                                std::process::exit(1);
+                            }
+                        };
+
+                        // Close splash and show main window
+                        if let Some(splash) = splash_handle {
+                            let _ = splash.close();
+                        }
+                        let _ = main_window.show();
+                        if dev_mode() {
+                            main_window.open_devtools();
+                        }
+
+                        // Off the startup thread, which goes on to supervise the backend
+                        #[cfg(target_os = "macos")]
+                        {
+                            let handle = handle.clone();
+                            std::thread::spawn(move || check_input_permissions(&handle));
+                        }
+
+                        if external {
+                            return;
+                        }
+                        supervise_until_stopped(&handle, exited);
+                    }
+                    Err(headline) => {
+                        let output = handle.state::<BackendLog>().recent_lines();
+                        if !output.is_empty() {
+                            log::error!("Last backend output:\n{}", output.join("\n"));
+                        }
+
+                        // Show the error on the splash screen behind the dialog
+                        if let Some(splash) = splash_handle {
+                            let _ = splash.eval(
+                                "document.getElementById('status').textContent = 'Failed to start.';\
+                                 document.getElementById('status').style.color = '#ef4444';"
+                            );
+                        }
+                        report_backend_error(&handle, &headline);
+                        shutdown_backend(&handle);
+                        std::process::exit(1);
+                    }
                 }
             });
 
@@ -167,47 +1731,209 @@ pub fn run() {
         .build(tauri::generate_context!())
         .expect("Error while building trenino")
         .run(|app_handle, event| {
-            if let tauri::RunEvent::ExitRequested { .. } = event {
-                // Gracefully shut down the backend by calling the shutdown endpoint
-                println!("App exit requested, initiating graceful backend shutdown");
-
-                let shutdown_url = format!("http://localhost:{}/api/shutdown", BACKEND_PORT);
-                let mut shutdown_succeeded = false;
-
-                // Send POST request to shutdown endpoint
-                // This tells the Elixir backend to call System.stop(0) which
-                // gracefully shuts down the Erlang VM and all child processes
-                match reqwest::blocking::Client::new()
-                    .post(&shutdown_url)
-                    .timeout(Duration::from_secs(2))
-                    .send()
-                {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            println!("Backend shutdown initiated successfully");
-                            shutdown_succeeded = true;
-                        } else {
-                            println!("Backend shutdown returned status: {}", response.status());
-                        }
-                    }
-                    Err(e) => {
-                        // Backend might already be down or unreachable
-                        println!("Could not reach backend for shutdown: {}", e);
-                    }
-                }
+            if let tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit = event {
+                shutdown_backend(app_handle);
+            }
+        });
+}
 
-                // If graceful shutdown succeeded, give it time to complete
-                // Otherwise, fall back to killing the process directly
-                if shutdown_succeeded {
-                    std::thread::sleep(Duration::from_millis(500));
-                } else if let Some(backend_state) = app_handle.try_state::<BackendProcess>() {
-                    if let Ok(mut guard) = backend_state.0.lock() {
-                        if let Some(child) = guard.take() {
-                            println!("Falling back to forceful process termination");
-                            let _ = child.kill();
-                        }
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Arc;
+
+    /// Serve the health endpoint on a free port, answering 503 "unavailable"
+    /// to the first `ready_after` requests and then "ok" (never if `None`).
+    /// Returns the base URL and the number of requests served so far.
+    fn mock_backend(ready_after: Option<u32>) -> (String, Arc<AtomicU32>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let polls = Arc::new(AtomicU32::new(0));
+        let served = polls.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                // Read the request up to the blank line ending its headers
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                    line.clear();
                 }
+
+                let poll = served.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = match ready_after {
+                    Some(n) if poll >= n => ("200 OK", r#"{"status":"ok"}"#),
+                    _ => ("503 Service Unavailable", r#"{"status":"unavailable"}"#),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
             }
         });
+
+        (url, polls)
+    }
+
+    fn policy(timeout_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(timeout_ms),
+            initial_delay: Duration::from_millis(5),
+            max_delay: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn detects_readiness_after_some_polls() {
+        let (url, polls) = mock_backend(Some(3));
+        let (_exit_tx, exited) = mpsc::channel();
+
+        let mut checks = Vec::new();
+        let startup = poll_until_ready(&url, &policy(5000), &exited, |progress, ready| {
+            checks.push((progress.attempt, ready))
+        });
+
+        assert!(matches!(startup, Startup::Ready));
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+        assert_eq!(checks, [(1, false), (2, false), (3, false), (4, true)]);
+    }
+
+    #[test]
+    fn times_out_when_never_ready() {
+        let (url, polls) = mock_backend(None);
+        let (_exit_tx, exited) = mpsc::channel();
+
+        let started = Instant::now();
+        let startup = poll_until_ready(&url, &policy(200), &exited, |progress, ready| {
+            assert!(!ready);
+            assert!(progress.attempt <= progress.max);
+        });
+
+        assert!(matches!(startup, Startup::TimedOut));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(polls.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn counts_the_checks_that_fit_the_timeout() {
+        // Checks after waiting 0, 5, 15, 35, 55, 75 and 95ms
+        assert_eq!(policy(100).max_attempts(), 7);
+        assert_eq!(policy(1).max_attempts(), 1);
+    }
+
+    #[test]
+    fn jitter_only_lengthens_a_little() {
+        let delay = Duration::from_millis(1000);
+        for _ in 0..100 {
+            let jittered = jittered(delay);
+            assert!(jittered >= delay);
+            assert!(jittered <= delay.mul_f64(1.0 + RETRY_JITTER));
+        }
+    }
+
+    #[test]
+    fn health_check_semantics() {
+        let ok = r#"{"status":"ok"}"#;
+        let unavailable = r#"{"status":"unavailable"}"#;
+        let default = HealthCheck {
+            method: reqwest::Method::GET,
+            status: None,
+        };
+        assert_eq!(default.is_ready(200, ok), Ok(true));
+        assert_eq!(default.is_ready(503, unavailable), Ok(false));
+        assert_eq!(default.is_ready(200, "<html>"), Err(()));
+
+        let head = HealthCheck {
+            method: reqwest::Method::HEAD,
+            status: None,
+        };
+        assert_eq!(head.is_ready(200, ""), Ok(true));
+        assert_eq!(head.is_ready(502, ""), Ok(false));
+
+        let no_content = HealthCheck {
+            method: reqwest::Method::GET,
+            status: Some(204),
+        };
+        assert_eq!(no_content.is_ready(204, ""), Ok(true));
+        assert_eq!(no_content.is_ready(200, ok), Ok(false));
+        assert_eq!(no_content.is_ready(204, unavailable), Ok(false));
+    }
+
+    #[test]
+    fn detects_port_conflicts() {
+        assert!(is_port_conflict(
+            "** (Mix) Could not start application trenino: ... {:shutdown, {:failed_to_start_child, :listener, :eaddrinuse}}"
+        ));
+        assert!(is_port_conflict("Error: listen EADDRINUSE: address already in use 127.0.0.1:4000"));
+        assert!(!is_port_conflict("[info] Running TreninoWeb.Endpoint with Bandit at 127.0.0.1:4000"));
+    }
+
+    #[test]
+    fn keystroke_keeps_uppercase_keys() {
+        assert_eq!(
+            keystroke_args("tap", "CTRL+S"),
+            ["tap", "--no-auto-shift", "--", "CTRL+S"]
+        );
+        assert_eq!(
+            keystroke_args("tap", "--window"),
+            ["tap", "--no-auto-shift", "--", "--window"]
+        );
+    }
+
+    #[test]
+    fn keystroke_errors_by_exit_code() {
+        let error = |code| keystroke_error(code, "W", "oops");
+        assert_eq!(error(Some(10)), "Invalid keystroke 'W': oops");
+        assert_eq!(error(Some(20)), "Could not send keystroke 'W': oops");
+        assert_eq!(error(Some(30)), "Not allowed to send keystroke 'W': oops");
+        assert_eq!(error(Some(40)), "No window to send keystroke 'W' to: oops");
+        assert_eq!(error(Some(1)), "keystroke exited with code Some(1): oops");
+        assert_eq!(error(None), "keystroke exited with code None: oops");
+    }
+
+    #[test]
+    fn picks_the_version_line() {
+        assert_eq!(
+            version_line("keystroke 0.1.0\n").as_deref(),
+            Some("keystroke 0.1.0")
+        );
+        let usage = "Usage: avrdude [options]\n  -p <partno>\n\navrdude version 7.3, https://github.com/avrdudes/avrdude\n";
+        assert_eq!(
+            version_line(usage).as_deref(),
+            Some("avrdude version 7.3, https://github.com/avrdudes/avrdude")
+        );
+        assert_eq!(version_line(" \n"), None);
+    }
+
+    #[test]
+    fn times_out_when_nothing_listens() {
+        // Bind and drop a listener to find a port nothing is listening on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (_exit_tx, exited) = mpsc::channel();
+
+        let startup = poll_until_ready(
+            &format!("http://127.0.0.1:{}", port),
+            &policy(100),
+            &exited,
+            |_, ready| assert!(!ready),
+        );
+        assert!(matches!(startup, Startup::TimedOut));
+    }
+
+    #[test]
+    fn stops_when_the_backend_exits() {
+        let (url, _) = mock_backend(None);
+        let (exit_tx, exited) = mpsc::channel();
+        exit_tx.send(Some(1)).unwrap();
+
+        let startup = poll_until_ready(&url, &policy(5000), &exited, |_, _| {});
+        assert!(matches!(startup, Startup::Exited(Some(1))));
+    }
 }
@@ -0,0 +1,107 @@
+//! Serial ports a Trenino device may be connected to.
+//!
+//! Probing a port means opening it, which on some systems pairs or wakes a
+//! Bluetooth device and on others ties up a debug console. Only USB ports are
+//! listed by default, since every supported board connects over USB.
+
+use serialport::SerialPortType;
+
+/// Ports whose names mark them as not being a board, whatever their type,
+/// the same patterns the backend ignores
+const IGNORED_NAMES: &[&str] = &["bluetooth", "debug", "tone"];
+
+/// A serial port as reported by `list_serial_ports`
+#[derive(serde::Serialize)]
+pub struct SerialPort {
+    /// Path or name to open it by, e.g. /dev/ttyACM0 or COM3
+    name: String,
+    /// How it is connected: "usb", "pci", "bluetooth" or "unknown"
+    kind: &'static str,
+    /// USB vendor and product ids, for recognizing boards
+    vid: Option<u16>,
+    pid: Option<u16>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial_number: Option<String>,
+}
+
+impl SerialPort {
+    fn new(name: String, port_type: SerialPortType) -> Self {
+        let mut port = Self {
+            name,
+            kind: kind(&port_type),
+            vid: None,
+            pid: None,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+        };
+        if let SerialPortType::UsbPort(usb) = port_type {
+            port.vid = Some(usb.vid);
+            port.pid = Some(usb.pid);
+            port.manufacturer = usb.manufacturer;
+            port.product = usb.product;
+            port.serial_number = usb.serial_number;
+        }
+        port
+    }
+}
+
+fn kind(port_type: &SerialPortType) -> &'static str {
+    match port_type {
+        SerialPortType::UsbPort(_) => "usb",
+        SerialPortType::PciPort => "pci",
+        SerialPortType::BluetoothPort => "bluetooth",
+        SerialPortType::Unknown => "unknown",
+    }
+}
+
+/// Whether a device could be on this port: a USB port with a name that
+/// doesn't mark it as something else
+fn is_device_port(name: &str, port_type: &SerialPortType) -> bool {
+    let name = name.to_lowercase();
+    matches!(port_type, SerialPortType::UsbPort(_))
+        && !IGNORED_NAMES.iter().any(|ignored| name.contains(ignored))
+}
+
+/// The serial ports on this machine, only those a device could be on unless
+/// `all` is set
+pub fn list(all: bool) -> Result<Vec<SerialPort>, String> {
+    let ports = serialport::available_ports()
+        .map_err(|e| format!("Could not list the serial ports: {}", e))?;
+    Ok(ports
+        .into_iter()
+        .filter(|port| all || is_device_port(&port.port_name, &port.port_type))
+        .map(|port| SerialPort::new(port.port_name, port.port_type))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_only_usb_ports() {
+        use serialport::UsbPortInfo;
+
+        let usb = SerialPortType::UsbPort(UsbPortInfo {
+            vid: 0x2341,
+            pid: 0x0043,
+            serial_number: None,
+            manufacturer: Some("Arduino".to_string()),
+            product: None,
+        });
+        assert!(is_device_port("/dev/ttyACM0", &usb));
+        assert!(is_device_port("COM3", &usb));
+        assert!(!is_device_port("/dev/cu.debug-console", &usb));
+        assert!(!is_device_port(
+            "/dev/cu.Bluetooth-Incoming-Port",
+            &SerialPortType::Unknown
+        ));
+        assert!(!is_device_port(
+            "/dev/rfcomm0",
+            &SerialPortType::BluetoothPort
+        ));
+        assert!(!is_device_port("/dev/ttyS0", &SerialPortType::PciPort));
+    }
+}
@@ -5,7 +5,10 @@ defmodule TreninoWeb.HealthControllerTest do
     test "returns 200 with ok status when database is ready", %{conn: conn} do
       conn = get(conn, ~p"/api/health")
 
-      assert json_response(conn, 200) == %{"status" => "ok"}
+      assert json_response(conn, 200) == %{
+               "status" => "ok",
+               "version" => Trenino.AppVersion.current_version()
+             }
     end
 
     test "response includes proper content type", %{conn: conn} do